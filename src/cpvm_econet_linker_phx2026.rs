@@ -1,12 +1,51 @@
+use std::collections::{HashMap, HashSet};
 use std::error::Error;
 use std::fmt;
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::{BufRead, BufReader, Seek, SeekFrom, Write};
 
 /// Identifier for a physical-virtual water asset node.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct NodeId(pub String);
 
+impl fmt::Display for NodeId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Administrative region a node reports under (e.g. a county or utility).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Region(pub String);
+
+impl fmt::Display for Region {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl From<&str> for Region {
+    fn from(s: &str) -> Self {
+        Region(s.to_string())
+    }
+}
+
+/// Named waterbody a node sits on (river, lake, aquifer).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Waterbody(pub String);
+
+impl fmt::Display for Waterbody {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl From<&str> for Waterbody {
+    fn from(s: &str) -> Self {
+        Waterbody(s.to_string())
+    }
+}
+
 /// Asset type taxonomy for CPVM–EcoNet nodes.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum AssetType {
@@ -19,18 +58,88 @@ pub enum AssetType {
 }
 
 impl AssetType {
+    /// Parse an asset type, ignoring surrounding whitespace and ASCII case.
+    /// Unknown types keep their original casing inside `Other`.
     pub fn from_str(s: &str) -> Self {
-        match s.trim() {
-            "Reservoir" => AssetType::Reservoir,
-            "Plant" => AssetType::Plant,
-            "RiverReach" => AssetType::RiverReach,
-            "Basin" => AssetType::Basin,
-            "WatershedCluster" => AssetType::WatershedCluster,
-            other => AssetType::Other(other.to_string()),
+        let s = s.trim();
+        match s.to_ascii_lowercase().as_str() {
+            "reservoir" => AssetType::Reservoir,
+            "plant" => AssetType::Plant,
+            "riverreach" => AssetType::RiverReach,
+            "basin" => AssetType::Basin,
+            "watershedcluster" => AssetType::WatershedCluster,
+            _ => AssetType::Other(s.to_string()),
+        }
+    }
+
+    /// Like [`from_str`](Self::from_str), but a string that would become
+    /// `Other` is first looked up in `aliases`, ignoring ASCII case.
+    ///
+    /// Start from [`default_asset_aliases`] and insert partner-specific
+    /// synonyms as needed.
+    pub fn from_str_with_aliases(s: &str, aliases: &HashMap<String, AssetType>) -> Self {
+        match AssetType::from_str(s) {
+            AssetType::Other(raw) => aliases
+                .iter()
+                .find(|(alias, _)| alias.eq_ignore_ascii_case(&raw))
+                .map(|(_, asset)| asset.clone())
+                .unwrap_or(AssetType::Other(raw)),
+            canonical => canonical,
+        }
+    }
+
+    /// Storage assets: `Reservoir`, `Basin`, or `WatershedCluster`.
+    pub fn is_storage(&self) -> bool {
+        matches!(
+            self,
+            AssetType::Reservoir | AssetType::Basin | AssetType::WatershedCluster
+        )
+    }
+
+    /// Flowing assets: `RiverReach`.
+    pub fn is_flowing(&self) -> bool {
+        matches!(self, AssetType::RiverReach)
+    }
+
+    /// Engineered assets: `Plant`.
+    pub fn is_engineered(&self) -> bool {
+        matches!(self, AssetType::Plant)
+    }
+
+    /// Exact token recognized by `from_str`; `Other(s)` yields `s`.
+    pub fn as_canonical_str(&self) -> &str {
+        match self {
+            AssetType::Reservoir => "Reservoir",
+            AssetType::Plant => "Plant",
+            AssetType::RiverReach => "RiverReach",
+            AssetType::Basin => "Basin",
+            AssetType::WatershedCluster => "WatershedCluster",
+            AssetType::Other(s) => s,
         }
     }
 }
 
+/// Common partner synonyms for canonical asset types, for
+/// [`AssetType::from_str_with_aliases`].
+pub fn default_asset_aliases() -> HashMap<String, AssetType> {
+    [
+        ("WWTP", AssetType::Plant),
+        ("WaterTreatmentPlant", AssetType::Plant),
+        ("Treatment", AssetType::Plant),
+        ("Lake", AssetType::Reservoir),
+        ("Dam", AssetType::Reservoir),
+    ]
+    .into_iter()
+    .map(|(alias, asset)| (alias.to_string(), asset))
+    .collect()
+}
+
+impl fmt::Display for AssetType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_canonical_str())
+    }
+}
+
 /// Simple unit enum for concentration.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum ConcentrationUnit {
@@ -41,39 +150,190 @@ pub enum ConcentrationUnit {
 }
 
 impl ConcentrationUnit {
+    /// Parse a concentration unit, ignoring ASCII case. The known tokens stay
+    /// unambiguous without case (`MG/L` can only mean mg/L here).
     pub fn from_str(s: &str) -> Self {
-        match s.trim() {
-            "ng/L" => ConcentrationUnit::NgPerL,
-            "mg/L" => ConcentrationUnit::MgPerL,
-            "MPN/100mL" => ConcentrationUnit::MpnPer100mL,
-            other => ConcentrationUnit::Other(other.to_string()),
+        let s = s.trim();
+        match s.to_ascii_lowercase().as_str() {
+            "ng/l" => ConcentrationUnit::NgPerL,
+            "mg/l" => ConcentrationUnit::MgPerL,
+            "mpn/100ml" => ConcentrationUnit::MpnPer100mL,
+            _ => ConcentrationUnit::Other(s.to_string()),
+        }
+    }
+
+    /// Exact token recognized by `from_str`; `Other(s)` yields `s`.
+    pub fn as_canonical_str(&self) -> &str {
+        match self {
+            ConcentrationUnit::NgPerL => "ng/L",
+            ConcentrationUnit::MgPerL => "mg/L",
+            ConcentrationUnit::MpnPer100mL => "MPN/100mL",
+            ConcentrationUnit::Other(s) => s,
+        }
+    }
+
+    /// Multiplicative factor converting a value in this unit to mg/L.
+    ///
+    /// Returns `None` for units that are not mass-based (e.g. MPN/100mL) or
+    /// that are not recognized.
+    pub fn to_mg_per_l_factor(&self) -> Option<f64> {
+        match self {
+            ConcentrationUnit::NgPerL => Some(1.0e-6),
+            ConcentrationUnit::MgPerL => Some(1.0),
+            ConcentrationUnit::MpnPer100mL => None,
+            ConcentrationUnit::Other(_) => None,
         }
     }
 }
 
+impl fmt::Display for ConcentrationUnit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_canonical_str())
+    }
+}
+
+/// Convert a concentration value between units.
+///
+/// Identical units always convert (including non-mass units such as
+/// MPN/100mL); otherwise both units must be mass-based. Returns `None` where
+/// the conversion is physically meaningless.
+pub fn convert_concentration(
+    value: f64,
+    from: &ConcentrationUnit,
+    to: &ConcentrationUnit,
+) -> Option<f64> {
+    if from == to {
+        return Some(value);
+    }
+    let from_factor = from.to_mg_per_l_factor()?;
+    let to_factor = to.to_mg_per_l_factor()?;
+    Some(value * from_factor / to_factor)
+}
+
 /// Simple unit enum for flow.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum FlowUnit {
     M3PerS,
+    LPerS,
+    /// Million US gallons per day.
+    MGD,
     Other(String),
 }
 
+/// Cubic meters per second in one million US gallons per day.
+const M3_PER_S_PER_MGD: f64 = 1.0e6 * 3.785_411_784e-3 / 86_400.0;
+
 impl FlowUnit {
+    /// Parse a flow unit, ignoring ASCII case. Prefixed units such as `mL/s`
+    /// are not recognized and fall back to `Other` rather than matching `L/s`.
     pub fn from_str(s: &str) -> Self {
-        match s.trim() {
+        let s = s.trim();
+        match s.to_ascii_lowercase().as_str() {
             "m3/s" => FlowUnit::M3PerS,
-            other => FlowUnit::Other(other.to_string()),
+            "l/s" => FlowUnit::LPerS,
+            "mgd" => FlowUnit::MGD,
+            _ => FlowUnit::Other(s.to_string()),
+        }
+    }
+
+    /// Exact token recognized by `from_str`; `Other(s)` yields `s`.
+    pub fn as_canonical_str(&self) -> &str {
+        match self {
+            FlowUnit::M3PerS => "m3/s",
+            FlowUnit::LPerS => "L/s",
+            FlowUnit::MGD => "MGD",
+            FlowUnit::Other(s) => s,
+        }
+    }
+
+    /// Multiplicative factor converting a value in this unit to m3/s.
+    ///
+    /// Returns `None` for unrecognized units.
+    pub fn to_m3_per_s_factor(&self) -> Option<f64> {
+        match self {
+            FlowUnit::M3PerS => Some(1.0),
+            FlowUnit::LPerS => Some(1.0e-3),
+            FlowUnit::MGD => Some(M3_PER_S_PER_MGD),
+            FlowUnit::Other(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for FlowUnit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_canonical_str())
+    }
+}
+
+/// Physical unit of an integrated mass load.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MassUnit {
+    Ng,
+    Ug,
+    Mg,
+    G,
+    Kg,
+    /// Non-mass concentration (e.g. MPN) or unresolvable units.
+    Unknown,
+}
+
+impl MassUnit {
+    /// Unit of `C [unit/L] * Q [m3/s] * t [s]`.
+    ///
+    /// One m3 is 1000 L, so a mg/L load integrates to grams and an ng/L load
+    /// to micrograms. Unknown flow units or non-mass concentrations resolve
+    /// to `Unknown`.
+    pub fn for_load(cin_unit: &ConcentrationUnit, q_unit: &FlowUnit) -> Self {
+        if q_unit.to_m3_per_s_factor().is_none() {
+            return MassUnit::Unknown;
+        }
+        match cin_unit {
+            ConcentrationUnit::NgPerL => MassUnit::Ug,
+            ConcentrationUnit::MgPerL => MassUnit::G,
+            ConcentrationUnit::MpnPer100mL | ConcentrationUnit::Other(_) => MassUnit::Unknown,
+        }
+    }
+
+    /// Short unit symbol, e.g. `"g"` or `"unknown"`.
+    pub fn as_canonical_str(&self) -> &'static str {
+        match self {
+            MassUnit::Ng => "ng",
+            MassUnit::Ug => "ug",
+            MassUnit::Mg => "mg",
+            MassUnit::G => "g",
+            MassUnit::Kg => "kg",
+            MassUnit::Unknown => "unknown",
+        }
+    }
+
+    /// Multiplicative factor converting a mass in this unit to kg.
+    pub fn to_kg_factor(&self) -> Option<f64> {
+        match self {
+            MassUnit::Ng => Some(1.0e-12),
+            MassUnit::Ug => Some(1.0e-9),
+            MassUnit::Mg => Some(1.0e-6),
+            MassUnit::G => Some(1.0e-3),
+            MassUnit::Kg => Some(1.0),
+            MassUnit::Unknown => None,
         }
     }
 }
 
+/// Co-contaminant tracked alongside the primary one (e.g. PFOA with PFBS).
+#[derive(Debug, Clone, PartialEq)]
+pub struct SecondaryContaminant {
+    pub name: String,
+    pub cin_baseline: f64,
+    pub cin_unit: ConcentrationUnit,
+}
+
 /// Core CPVM node metadata and baseline environmental state.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct CpvmNodeMeta {
     pub node_id: NodeId,
     pub asset_type: AssetType,
-    pub waterbody: String,
-    pub region: String,
+    pub waterbody: Waterbody,
+    pub region: Region,
     /// Name of the CPVM profile (e.g. PFAS_PFBS_LP_v1).
     pub cpvm_profile: String,
     /// Baseline inlet concentration C_in.
@@ -89,6 +349,19 @@ pub struct CpvmNodeMeta {
     /// Karma per unit canonical impact.
     pub karma_per_unit: f64,
     pub notes: String,
+    /// Free-form governance tags (e.g. `priority`, `pilot`), from an optional
+    /// pipe-delimited `tags` column.
+    pub tags: Vec<String>,
+    /// Optional co-contaminant from the `sec_name`, `sec_cin`, and `sec_unit`
+    /// columns; `None` when the columns are absent or `sec_name` is blank.
+    pub secondary: Option<SecondaryContaminant>,
+    /// Reservoir residence time [s] from the optional `residence_time_s`
+    /// column; see [`effective_horizon`].
+    pub residence_time_s: Option<f64>,
+    /// WGS84 latitude in degrees from the optional `lat` column.
+    pub lat: Option<f64>,
+    /// WGS84 longitude in degrees from the optional `lon` column.
+    pub lon: Option<f64>,
 }
 
 /// CPVM safety configuration (per-node).
@@ -120,6 +393,22 @@ pub struct EcoImpactResult {
     pub ecoimpactscore: f64,
     /// Karma gain = ecoimpactscore * mass_avoided * karma_per_unit.
     pub karma_gain: f64,
+    /// Physical unit of `mass_avoided`, resolved from the node's units.
+    pub mass_unit: MassUnit,
+    /// Integration horizon used [s].
+    pub horizon_s: f64,
+    /// Discharge used, converted to m3/s (0.0 when the flow unit is unknown).
+    pub q_m3_per_s: f64,
+}
+
+impl fmt::Display for EcoImpactResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "mass={:.4e} score={:.3} karma={:.4e}",
+            self.mass_avoided, self.ecoimpactscore, self.karma_gain
+        )
+    }
 }
 
 /// Errors for CSV parsing and configuration.
@@ -127,6 +416,37 @@ pub struct EcoImpactResult {
 pub enum CpvmLinkerError {
     Io(std::io::Error),
     Parse(String),
+    /// Parse error tied to a shard row. `message` is the full text, already
+    /// naming the line, so `Display` matches [`CpvmLinkerError::Parse`].
+    ParseAt {
+        line: usize,
+        message: String,
+    },
+}
+
+/// Coarse error category for programmatic matching; see [`CpvmLinkerError::kind`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CpvmErrorKind {
+    Io,
+    Parse,
+}
+
+impl CpvmLinkerError {
+    /// Category of this error, so callers need not match on message text.
+    pub fn kind(&self) -> CpvmErrorKind {
+        match self {
+            CpvmLinkerError::Io(_) => CpvmErrorKind::Io,
+            CpvmLinkerError::Parse(_) | CpvmLinkerError::ParseAt { .. } => CpvmErrorKind::Parse,
+        }
+    }
+
+    /// 1-based shard line the error refers to, when known.
+    pub fn line(&self) -> Option<usize> {
+        match self {
+            CpvmLinkerError::ParseAt { line, .. } => Some(*line),
+            _ => None,
+        }
+    }
 }
 
 impl fmt::Display for CpvmLinkerError {
@@ -134,6 +454,7 @@ impl fmt::Display for CpvmLinkerError {
         match self {
             CpvmLinkerError::Io(e) => write!(f, "IO error: {}", e),
             CpvmLinkerError::Parse(e) => write!(f, "Parse error: {}", e),
+            CpvmLinkerError::ParseAt { message, .. } => write!(f, "Parse error: {}", message),
         }
     }
 }
@@ -149,266 +470,6473 @@ impl From<std::io::Error> for CpvmLinkerError {
 /// Parse a single CSV line into fields, honoring quoted segments.
 /// This keeps dependencies minimal for embedded environments.
 fn split_csv_line(line: &str) -> Vec<String> {
+    split_line_with_delimiter(line, ',')
+}
+
+/// Split a delimited line into fields, honoring quoted segments.
+/// A doubled quote (`""`) inside a quoted segment is an RFC-4180 escape and
+/// yields a single literal `"`; `delim` inside quotes is kept literally.
+///
+/// Never panics. An unterminated quote is closed at end of line: everything
+/// after the opening `"`, delimiters included, becomes the last field's text.
+/// Any non-empty input yields at least one field.
+fn split_line_with_delimiter(line: &str, delim: char) -> Vec<String> {
+    split_line_with(line, delim, true)
+}
+
+/// [`split_line_with_delimiter`] with optional field trimming. With `trim`
+/// false only the quote characters are removed; all whitespace is kept.
+fn split_line_with(line: &str, delim: char, trim: bool) -> Vec<String> {
+    let finish = |field: &str| {
+        if trim {
+            field.trim().to_string()
+        } else {
+            field.to_string()
+        }
+    };
     let mut fields = Vec::new();
     let mut current = String::new();
     let mut in_quotes = false;
+    let mut chars = line.chars().peekable();
 
-    for c in line.chars() {
+    while let Some(c) = chars.next() {
         match c {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                current.push('"');
+                chars.next();
+            }
             '"' => {
                 in_quotes = !in_quotes;
             }
-            ',' if !in_quotes => {
-                fields.push(current.trim().to_string());
+            c if c == delim && !in_quotes => {
+                fields.push(finish(&current));
                 current.clear();
             }
             _ => current.push(c),
         }
     }
-    if !current.is_empty() || line.ends_with(',') {
-        fields.push(current.trim().to_string());
+    // Any non-empty line ends with one more field, even when that field is
+    // empty (trailing comma) or an empty quoted segment (`""`).
+    if !line.is_empty() {
+        fields.push(finish(&current));
     }
     fields
 }
 
-/// Load CPVM–EcoNet qpudatashard CSV into structured node metadata.
-pub fn load_cpvm_nodes_from_csv(path: &str) -> Result<Vec<CpvmNodeMeta>, CpvmLinkerError> {
-    let file = File::open(path)?;
-    let reader = BufReader::new(file);
-
-    let mut lines = reader.lines();
+/// Shard parsing options for [`load_cpvm_nodes_with_options`].
+#[derive(Debug, Clone)]
+pub struct LoaderOptions {
+    /// Field delimiter, e.g. `','`, `'\t'`, or `';'`.
+    pub delimiter: char,
+    /// Whether the first line is a header row. Headerless shards use the
+    /// legacy positional column order with `notes` last.
+    pub has_header: bool,
+    /// Lines whose first non-whitespace text is this prefix are skipped,
+    /// before and after the header. `None` disables comment handling.
+    pub comment_prefix: Option<String>,
+    /// Decimal separator for numeric fields, `'.'` or `','`. The other of
+    /// the two is treated as a thousands separator; see [`parse_f64_loose_with`].
+    pub decimal_separator: char,
+    /// Reject rows with more fields than the header declares instead of
+    /// ignoring the extras. Ignored for headerless shards.
+    pub strict: bool,
+    /// Trim whitespace around each data field. When false, only surrounding
+    /// quotes are stripped, so `" A "` keeps its spaces. Header names are
+    /// always trimmed.
+    pub trim_fields: bool,
+    /// Header spellings accepted for each canonical column.
+    pub column_aliases: ColumnAliases,
+    /// Characters stripped from both ends of numeric and duration fields in
+    /// addition to whitespace, e.g. `'\u{200B}'` (zero-width space). NBSP is
+    /// already Unicode whitespace. Empty by default.
+    pub extra_trim_chars: Vec<char>,
+}
 
-    // Skip header
-    let _header = match lines.next() {
-        Some(Ok(h)) => h,
-        Some(Err(e)) => return Err(CpvmLinkerError::Io(e)),
-        None => return Ok(Vec::new()),
-    };
+impl LoaderOptions {
+    /// `raw` with whitespace and [`extra_trim_chars`](Self::extra_trim_chars)
+    /// removed from both ends; unchanged when no extra characters are set.
+    fn trim_numeric<'a>(&self, raw: &'a str) -> &'a str {
+        if self.extra_trim_chars.is_empty() {
+            raw
+        } else {
+            raw.trim_matches(|c: char| c.is_whitespace() || self.extra_trim_chars.contains(&c))
+        }
+    }
 
-    let mut nodes = Vec::new();
+    /// Whether an optional cell counts as absent: empty once whitespace and
+    /// [`extra_trim_chars`](Self::extra_trim_chars) are removed, regardless of
+    /// [`trim_fields`](Self::trim_fields).
+    fn is_blank_cell(&self, raw: &str) -> bool {
+        raw.chars()
+            .all(|c| c.is_whitespace() || self.extra_trim_chars.contains(&c))
+    }
+}
 
-    for (idx, line_res) in lines.enumerate() {
-        let line = line_res?;
-        if line.trim().is_empty() {
-            continue;
+impl Default for LoaderOptions {
+    fn default() -> Self {
+        LoaderOptions {
+            delimiter: ',',
+            has_header: true,
+            comment_prefix: Some("#".to_string()),
+            decimal_separator: '.',
+            strict: false,
+            trim_fields: true,
+            column_aliases: ColumnAliases::default(),
+            extra_trim_chars: Vec::new(),
         }
-        let fields = split_csv_line(&line);
-        if fields.len() < 12 {
-            return Err(CpvmLinkerError::Parse(format!(
-                "Line {} has insufficient fields: {}",
-                idx + 2,
-                fields.len()
-            )));
-        }
-
-        let node_id = NodeId(fields[0].to_string());
-        let asset_type = AssetType::from_str(&fields[1]);
-        let waterbody = fields[2].to_string();
-        let region = fields[3].to_string();
-        let cpvm_profile = fields[4].to_string();
+    }
+}
 
-        let cin_baseline: f64 = fields[5]
-            .parse()
-            .map_err(|e| CpvmLinkerError::Parse(format!("cin_baseline parse error: {}", e)))?;
-        let cin_unit = ConcentrationUnit::from_str(&fields[6]);
+/// Canonical column names every CPVM–EcoNet shard header must provide.
+const REQUIRED_COLUMNS: [&str; 12] = [
+    "node_id",
+    "asset_type",
+    "waterbody",
+    "region",
+    "cpvm_profile",
+    "cin_baseline",
+    "cin_unit",
+    "q_avg",
+    "q_unit",
+    "horizon_s",
+    "ecoimpactscore",
+    "karma_per_unit",
+];
 
-        let q_avg: f64 = fields[7]
-            .parse()
-            .map_err(|e| CpvmLinkerError::Parse(format!("q_avg parse error: {}", e)))?;
-        let q_unit = FlowUnit::from_str(&fields[8]);
+/// Optional columns recognized by name in a shard header.
+const OPTIONAL_COLUMNS: [&str; 8] = [
+    "notes",
+    "tags",
+    "sec_name",
+    "sec_cin",
+    "sec_unit",
+    "residence_time_s",
+    "lat",
+    "lon",
+];
 
-        let horizon_s: f64 = fields[9]
-            .parse()
-            .map_err(|e| CpvmLinkerError::Parse(format!("horizon_s parse error: {}", e)))?;
+/// Accepted header strings for each canonical column, for partner shards
+/// with abbreviated or translated headers (`id`, `typ`, `q`).
+///
+/// The default accepts exactly the canonical names; aliases are added on
+/// top, so canonical headers keep working.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnAliases {
+    accepted: HashMap<String, Vec<String>>,
+}
 
-        let ecoimpactscore: f64 = fields[10]
-            .parse()
-            .map_err(|e| CpvmLinkerError::Parse(format!("ecoimpactscore parse error: {}", e)))?;
+impl Default for ColumnAliases {
+    fn default() -> Self {
+        let accepted = REQUIRED_COLUMNS
+            .iter()
+            .chain(OPTIONAL_COLUMNS.iter())
+            .map(|name| (name.to_string(), vec![name.to_string()]))
+            .collect();
+        ColumnAliases { accepted }
+    }
+}
 
-        let karma_per_unit: f64 = fields[11]
-            .parse()
-            .map_err(|e| CpvmLinkerError::Parse(format!("karma_per_unit parse error: {}", e)))?;
+impl ColumnAliases {
+    /// Also accept `header` as the name of canonical column `canonical`.
+    pub fn with_alias(mut self, canonical: &str, header: &str) -> Self {
+        self.accepted
+            .entry(canonical.to_string())
+            .or_default()
+            .push(header.to_string());
+        self
+    }
 
-        let notes = if fields.len() > 12 {
-            fields[12..].join(",")
-        } else {
-            String::new()
-        };
+    /// Header strings accepted for `canonical`; empty if it has none.
+    pub fn accepted(&self, canonical: &str) -> &[String] {
+        self.accepted.get(canonical).map_or(&[], Vec::as_slice)
+    }
 
-        nodes.push(CpvmNodeMeta {
-            node_id,
-            asset_type,
-            waterbody,
-            region,
-            cpvm_profile,
-            cin_baseline,
-            cin_unit,
-            q_avg,
-            q_unit,
-            horizon_s,
-            ecoimpactscore,
-            karma_per_unit,
-            notes,
-        });
+    /// Canonical column named by `header`, if any alias matches.
+    fn canonical_for(&self, header: &str) -> Option<&str> {
+        self.accepted
+            .iter()
+            .find(|(_, names)| names.iter().any(|n| n == header))
+            .map(|(canonical, _)| canonical.as_str())
     }
+}
 
-    Ok(nodes)
+/// Parsed shard header: column name → field index.
+struct ShardHeader {
+    columns: HashMap<String, usize>,
+    width: usize,
+    /// Fields a row needs to reach every required column.
+    min_fields: usize,
 }
 
-/// Construct a node-specific CPVM safety config from domain rules.
-///
-/// This function is intentionally simple and deterministic so that higher-level
-/// governance logic can override or wrap it as needed.
-pub fn derive_safety_config(
-    meta: &CpvmNodeMeta,
-    cref_default: f64,
-    lambda_clf: f64,
-    mu_cbf: f64,
-) -> CpvmSafetyConfig {
-    // The default safe_threshold is the baseline if nothing else is provided.
-    // In a full stack, this would be min(EPA, EU, WHO, etc.) and possibly lower than baseline.
-    let safe_threshold = meta.cin_baseline.min(cref_default);
+impl ShardHeader {
+    /// Parse a header line, erroring if any required column is missing.
+    ///
+    /// Header names are mapped to canonical columns through `aliases`;
+    /// unrecognized names are kept verbatim.
+    fn parse(line: &str, delim: char, aliases: &ColumnAliases) -> Result<Self, CpvmLinkerError> {
+        let names = split_line_with_delimiter(line, delim);
+        let width = names.len();
+        let mut columns = HashMap::new();
+        for (idx, name) in names.into_iter().enumerate() {
+            let name = match aliases.canonical_for(&name) {
+                Some(canonical) => canonical.to_string(),
+                None => name,
+            };
+            columns.entry(name).or_insert(idx);
+        }
+        let mut min_fields = 0;
+        for name in REQUIRED_COLUMNS {
+            match columns.get(name) {
+                Some(&idx) => min_fields = min_fields.max(idx + 1),
+                None => {
+                    return Err(CpvmLinkerError::Parse(format!(
+                        "Header is missing required column: {}",
+                        name
+                    )))
+                }
+            }
+        }
+        Ok(ShardHeader {
+            columns,
+            width,
+            min_fields,
+        })
+    }
 
-    CpvmSafetyConfig {
-        safe_threshold,
-        cref: cref_default,
-        lambda_clf,
-        mu_cbf,
+    /// Positional mapping for headerless legacy shards.
+    fn legacy() -> Self {
+        let columns: HashMap<String, usize> = REQUIRED_COLUMNS
+            .iter()
+            .chain(["notes"].iter())
+            .enumerate()
+            .map(|(idx, name)| (name.to_string(), idx))
+            .collect();
+        let width = columns.len();
+        ShardHeader {
+            columns,
+            width,
+            min_fields: REQUIRED_COLUMNS.len(),
+        }
+    }
+
+    /// Field for a named column, or "" when the column or field is absent.
+    fn field<'a>(&self, fields: &'a [String], name: &str) -> &'a str {
+        self.columns
+            .get(name)
+            .and_then(|&idx| fields.get(idx))
+            .map_or("", |f| f.as_str())
     }
 }
 
-/// Create a fully bound CPVM node configuration from metadata and global defaults.
+/// Tolerant numeric parser using `.` as the decimal separator.
 ///
-/// In the full Cybercore-Brain stack, per-profile C_ref and weights would be
-/// injected from CEIM‑XJ governance logic; this function provides a sane core.
-pub fn bind_cpvm_config(
-    meta: CpvmNodeMeta,
-    cref_default: f64,
-    lambda_clf: f64,
-    mu_cbf: f64,
-) -> CpvmNodeConfig {
-    let safety = derive_safety_config(&meta, cref_default, lambda_clf, mu_cbf);
-    CpvmNodeConfig { meta, safety }
+/// Accepts scientific notation (`1e3`) and strips `_` and `,` thousands
+/// separators (`1_000`, `1,234.5`). A `,` is only accepted between 3-digit
+/// groups of the integer part, so `"3,5"` is an error rather than 35.
+pub fn parse_f64_loose(s: &str) -> Result<f64, CpvmLinkerError> {
+    parse_f64_loose_with(s, '.')
 }
 
-/// Compute mass load avoided M = (C_in - C_out) * Q * t, using consistent units.
+/// [`parse_f64_loose`] with a configurable decimal separator.
 ///
-/// This assumes:
-/// - C_in and C_out share the same concentration units.
-/// - Q in m3/s, t in s.
-/// - For Karma accounting, *relative* magnitude matters; absolute unit conversion
-///   is handled at governance level if needed.
-pub fn compute_mass_avoided(
-    cin: f64,
-    cout: f64,
-    q_m3_per_s: f64,
-    horizon_s: f64,
-) -> f64 {
-    let delta_c = (cin - cout).max(0.0);
-    delta_c * q_m3_per_s * horizon_s
+/// With `','` as decimal, `.` becomes the thousands separator, so
+/// `1.234,5` parses as 1234.5. `_` is always ignored. Thousands separators
+/// must sit between well-formed 3-digit groups (`1,234,567`); anything else
+/// is an `invalid number` error.
+pub fn parse_f64_loose_with(s: &str, decimal_separator: char) -> Result<f64, CpvmLinkerError> {
+    let thousands = if decimal_separator == ',' { '.' } else { ',' };
+    let no_underscores: String = s.trim().chars().filter(|&c| c != '_').collect();
+    let ungrouped =
+        strip_thousands(&no_underscores, thousands, decimal_separator).ok_or_else(|| {
+            CpvmLinkerError::Parse(format!(
+                "invalid number {:?}: misplaced thousands separator {:?}",
+                s, thousands
+            ))
+        })?;
+    let cleaned: String = ungrouped
+        .chars()
+        .map(|c| if c == decimal_separator { '.' } else { c })
+        .collect();
+    cleaned
+        .parse()
+        .map_err(|e| CpvmLinkerError::Parse(format!("invalid number {:?}: {}", s, e)))
 }
 
-/// Evaluate eco-impact and Karma for a CPVM-controlled node over its horizon.
+/// Remove `sep` from the integer part of `s` if it only separates 3-digit
+/// groups (`-1,234,567.5`); `None` if `sep` appears anywhere else.
+fn strip_thousands(s: &str, sep: char, decimal_separator: char) -> Option<String> {
+    let int_end = s.find([decimal_separator, 'e', 'E']).unwrap_or(s.len());
+    let (int_part, rest) = s.split_at(int_end);
+    if rest.contains(sep) {
+        return None;
+    }
+    if !int_part.contains(sep) {
+        return Some(s.to_string());
+    }
+    let digits = int_part.trim_start_matches(['+', '-']);
+    let sign = &int_part[..int_part.len() - digits.len()];
+    let mut groups = digits.split(sep);
+    let lead = groups.next()?;
+    let is_digits = |g: &str| g.chars().all(|c| c.is_ascii_digit());
+    if lead.is_empty() || lead.len() > 3 || !is_digits(lead) {
+        return None;
+    }
+    let mut out = format!("{}{}", sign, lead);
+    for group in groups {
+        if group.len() != 3 || !is_digits(group) {
+            return None;
+        }
+        out.push_str(group);
+    }
+    out.push_str(rest);
+    Some(out)
+}
+
+/// Parse a duration such as `"24h"`, `"30d"`, or `"3600"` into seconds.
 ///
-/// This function is the core bridge: controllers can propose C_out, and this
-/// returns the resulting mass avoided and Karma gain consistent with CEIM-style
-/// ecoimpactscore and Karma-per-unit configuration.
-pub fn evaluate_ecoimpact_for_node(
-    cfg: &CpvmNodeConfig,
-    cout: f64,
-) -> EcoImpactResult {
-    let meta = &cfg.meta;
-    let q_m3_per_s = match meta.q_unit {
-        FlowUnit::M3PerS => meta.q_avg,
-        FlowUnit::Other(_) => meta.q_avg, // assume upstream has normalized if using nonstandard units
+/// Accepts suffixes `s`, `m`, `h`, `d`, and `y` (365 days); a bare number is
+/// taken as seconds. The numeric part goes through [`parse_f64_loose`].
+pub fn parse_duration_to_seconds(s: &str) -> Result<f64, CpvmLinkerError> {
+    parse_duration_with(s, '.')
+}
+
+fn parse_duration_with(s: &str, decimal_separator: char) -> Result<f64, CpvmLinkerError> {
+    let trimmed = s.trim();
+    let (number, scale) = match trimmed.char_indices().last() {
+        Some((idx, 's')) => (&trimmed[..idx], 1.0),
+        Some((idx, 'm')) => (&trimmed[..idx], 60.0),
+        Some((idx, 'h')) => (&trimmed[..idx], 3_600.0),
+        Some((idx, 'd')) => (&trimmed[..idx], 86_400.0),
+        Some((idx, 'y')) => (&trimmed[..idx], 365.0 * 86_400.0),
+        _ => (trimmed, 1.0),
     };
+    parse_f64_loose_with(number, decimal_separator)
+        .map(|v| v * scale)
+        .map_err(|_| CpvmLinkerError::Parse(format!("invalid duration {:?}", s)))
+}
 
-    let mass_avoided = compute_mass_avoided(
-        meta.cin_baseline,
-        cout,
-        q_m3_per_s,
-        meta.horizon_s,
-    );
+/// Wrap a field-level parse failure with the line number and column name.
+fn field_parse_error(line_no: usize, name: &str, err: CpvmLinkerError) -> CpvmLinkerError {
+    match err {
+        CpvmLinkerError::Parse(msg) => CpvmLinkerError::ParseAt {
+            line: line_no,
+            message: format!("Line {}: {} parse error: {}", line_no, name, msg),
+        },
+        other => other,
+    }
+}
 
-    let ecoimpactscore = meta.ecoimpactscore.clamp(0.0, 1.0);
-    let karma_gain = ecoimpactscore * mass_avoided * meta.karma_per_unit;
+/// Parse a named numeric column, tagging errors with the column name.
+fn parse_f64_field(
+    header: &ShardHeader,
+    fields: &[String],
+    name: &str,
+    line_no: usize,
+    options: &LoaderOptions,
+) -> Result<f64, CpvmLinkerError> {
+    let raw = options.trim_numeric(header.field(fields, name));
+    parse_f64_loose_with(raw, options.decimal_separator)
+        .map_err(|e| field_parse_error(line_no, name, e))
+}
 
-    EcoImpactResult {
-        mass_avoided,
+/// Parse one data row into node metadata using the header's column mapping.
+fn parse_node_row(
+    header: &ShardHeader,
+    fields: &[String],
+    line_no: usize,
+    options: &LoaderOptions,
+) -> Result<CpvmNodeMeta, CpvmLinkerError> {
+    if fields.len() < header.min_fields {
+        return Err(CpvmLinkerError::ParseAt {
+            line: line_no,
+            message: format!(
+                "Line {} has insufficient fields: {} (expected at least {})",
+                line_no,
+                fields.len(),
+                header.min_fields
+            ),
+        });
+    }
+    if options.strict && options.has_header && fields.len() > header.width {
+        return Err(CpvmLinkerError::ParseAt {
+            line: line_no,
+            message: format!(
+                "Line {} has too many fields: {} (expected {})",
+                line_no,
+                fields.len(),
+                header.width
+            ),
+        });
+    }
+
+    let node_id = NodeId(header.field(fields, "node_id").to_string());
+    let asset_type = AssetType::from_str(header.field(fields, "asset_type"));
+    let waterbody = Waterbody::from(header.field(fields, "waterbody"));
+    let region = Region::from(header.field(fields, "region"));
+    let cpvm_profile = header.field(fields, "cpvm_profile").to_string();
+
+    let cin_baseline = parse_f64_field(header, fields, "cin_baseline", line_no, options)?;
+    let cin_unit = ConcentrationUnit::from_str(header.field(fields, "cin_unit"));
+
+    let q_avg = parse_f64_field(header, fields, "q_avg", line_no, options)?;
+    let q_unit = FlowUnit::from_str(header.field(fields, "q_unit"));
+
+    let horizon_raw = options.trim_numeric(header.field(fields, "horizon_s"));
+    let horizon_s = parse_duration_with(horizon_raw, options.decimal_separator)
+        .map_err(|e| field_parse_error(line_no, "horizon_s", e))?;
+    let ecoimpactscore = parse_f64_field(header, fields, "ecoimpactscore", line_no, options)?;
+    let karma_per_unit = parse_f64_field(header, fields, "karma_per_unit", line_no, options)?;
+
+    // With a header, `notes` is one parsed field; quote it to embed commas.
+    // Legacy headerless shards re-join unquoted commas that spill past the
+    // last column.
+    let notes = match header.columns.get("notes") {
+        Some(&idx) if idx < fields.len() && !options.has_header => fields[idx..].join(","),
+        Some(&idx) if idx < fields.len() => fields[idx].clone(),
+        _ => String::new(),
+    };
+    let tags = parse_tags(header.field(fields, "tags"));
+
+    let secondary = match header.field(fields, "sec_name") {
+        name if options.is_blank_cell(name) => None,
+        name => Some(SecondaryContaminant {
+            name: name.to_string(),
+            cin_baseline: parse_f64_field(header, fields, "sec_cin", line_no, options)?,
+            cin_unit: ConcentrationUnit::from_str(header.field(fields, "sec_unit")),
+        }),
+    };
+    let residence_time_s = match header.field(fields, "residence_time_s") {
+        raw if options.is_blank_cell(raw) => None,
+        raw => Some(
+            parse_duration_with(options.trim_numeric(raw), options.decimal_separator)
+                .map_err(|e| field_parse_error(line_no, "residence_time_s", e))?,
+        ),
+    };
+    let lat = parse_coordinate_field(header, fields, "lat", 90.0, line_no, options)?;
+    let lon = parse_coordinate_field(header, fields, "lon", 180.0, line_no, options)?;
+
+    Ok(CpvmNodeMeta {
+        node_id,
+        asset_type,
+        waterbody,
+        region,
+        cpvm_profile,
+        cin_baseline,
+        cin_unit,
+        q_avg,
+        q_unit,
+        horizon_s,
         ecoimpactscore,
-        karma_gain,
+        karma_per_unit,
+        notes,
+        tags,
+        secondary,
+        residence_time_s,
+        lat,
+        lon,
+    })
+}
+
+/// Optional coordinate column: `None` when blank, an error outside
+/// `[-limit, limit]` degrees.
+fn parse_coordinate_field(
+    header: &ShardHeader,
+    fields: &[String],
+    name: &str,
+    limit: f64,
+    line_no: usize,
+    options: &LoaderOptions,
+) -> Result<Option<f64>, CpvmLinkerError> {
+    if options.is_blank_cell(header.field(fields, name)) {
+        return Ok(None);
+    }
+    let value = parse_f64_field(header, fields, name, line_no, options)?;
+    if !(-limit..=limit).contains(&value) {
+        return Err(CpvmLinkerError::ParseAt {
+            line: line_no,
+            message: format!(
+                "Line {}: {} = {} must be in [{}, {}]",
+                line_no, name, value, -limit, limit
+            ),
+        });
     }
+    Ok(Some(value))
 }
 
-/// Example helper: build configs for all nodes from a qpudatashard path.
-///
-/// Callers can then wire these configs into local controllers, smart-city
-/// orchestrators, or basin-scale optimizers.
-pub fn build_cpvm_configs_from_shard(
-    path: &str,
-    cref_default: f64,
-    lambda_clf: f64,
-    mu_cbf: f64,
-) -> Result<Vec<CpvmNodeConfig>, CpvmLinkerError> {
-    let metas = load_cpvm_nodes_from_csv(path)?;
-    let configs = metas
-        .into_iter()
-        .map(|m| bind_cpvm_config(m, cref_default, lambda_clf, mu_cbf))
-        .collect();
-    Ok(configs)
+/// Split a pipe-delimited `tags` field, dropping empty entries.
+fn parse_tags(raw: &str) -> Vec<String> {
+    raw.split('|')
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+        .map(str::to_string)
+        .collect()
 }
 
-/// Optional: small smoke test demonstrating loading and evaluation.
+/// Drop a leading UTF-8 byte-order mark, as written by Excel exports.
+fn strip_bom(line: &str) -> &str {
+    line.strip_prefix('\u{FEFF}').unwrap_or(line)
+}
+
+/// Streaming reader yielding one parsed node per shard data row.
 ///
-/// This is intentionally simple and can be moved into a proper test harness
-/// in a larger workspace.
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// The header is consumed eagerly on construction so that a malformed header
+/// fails fast; data rows are parsed lazily, keeping memory flat for very
+/// large shards.
+pub struct CpvmNodeReader<R: BufRead = BufReader<File>> {
+    reader: R,
+    header: Option<ShardHeader>,
+    options: LoaderOptions,
+    line_no: usize,
+    byte_offset: u64,
+}
+
+impl CpvmNodeReader {
+    /// Open a shard file and consume its header row.
+    pub fn open(path: &str) -> Result<Self, CpvmLinkerError> {
+        CpvmNodeReader::open_with_options(path, LoaderOptions::default())
+    }
+
+    /// Open a shard file with explicit parsing options.
+    pub fn open_with_options(path: &str, options: LoaderOptions) -> Result<Self, CpvmLinkerError> {
+        let file = File::open(path)?;
+        CpvmNodeReader::with_options(BufReader::new(file), options)
+    }
+}
+
+impl<R: BufRead> CpvmNodeReader<R> {
+    /// Wrap any buffered source (in-memory buffer, decompressed stream,
+    /// socket) and consume its header row.
+    pub fn from_reader(reader: R) -> Result<Self, CpvmLinkerError> {
+        CpvmNodeReader::with_options(reader, LoaderOptions::default())
+    }
+
+    /// Wrap a buffered source with explicit parsing options.
+    pub fn with_options(reader: R, options: LoaderOptions) -> Result<Self, CpvmLinkerError> {
+        let mut node_reader = CpvmNodeReader::with_header(reader, None, options);
+        node_reader.read_header(None)?;
+        Ok(node_reader)
+    }
+
+    /// Consume the header row (or install the legacy layout for headerless
+    /// shards). On error, [`line_number`](Self::line_number) is the line that
+    /// was being read.
+    fn read_header(
+        &mut self,
+        observer: Option<&dyn LoaderObserver>,
+    ) -> Result<(), CpvmLinkerError> {
+        self.header = if self.options.has_header {
+            match self.next_content_line_observed(observer) {
+                Some(Ok(h)) => {
+                    let options = &self.options;
+                    Some(ShardHeader::parse(
+                        &h,
+                        options.delimiter,
+                        &options.column_aliases,
+                    )?)
+                }
+                Some(Err(e)) => return Err(e),
+                None => None,
+            }
+        } else {
+            Some(ShardHeader::legacy())
+        };
+        Ok(())
+    }
+
+    /// Wrap a source positioned at its start without consuming a header;
+    /// with `header: None` the iterator yields nothing, but
+    /// [`next_content_line`](Self::next_content_line) still reads lines.
+    fn with_header(reader: R, header: Option<ShardHeader>, options: LoaderOptions) -> Self {
+        CpvmNodeReader {
+            reader,
+            header,
+            options,
+            line_no: 0,
+            byte_offset: 0,
+        }
+    }
+
+    /// Position just after the most recently read row, for resuming with
+    /// [`load_cpvm_nodes_from_checkpoint`].
+    pub fn checkpoint(&self) -> ShardCheckpoint {
+        ShardCheckpoint {
+            byte_offset: self.byte_offset,
+            line_number: self.line_no,
+        }
+    }
+
+    /// 1-based physical line number of the most recently read row.
+    pub fn line_number(&self) -> usize {
+        self.line_no
+    }
+
+    /// Bytes consumed from the source so far, counting from where reading
+    /// started; always on a line boundary.
+    pub fn byte_offset(&self) -> u64 {
+        self.byte_offset
+    }
+
+    /// Next physical line without its `\n` / `\r\n` terminator.
+    fn next_raw_line(&mut self) -> Option<Result<String, CpvmLinkerError>> {
+        let mut line = String::new();
+        match self.reader.read_line(&mut line) {
+            Ok(0) => None,
+            Ok(n) => {
+                self.byte_offset += n as u64;
+                if line.ends_with('\n') {
+                    line.pop();
+                    if line.ends_with('\r') {
+                        line.pop();
+                    }
+                }
+                Some(Ok(line))
+            }
+            Err(e) => Some(Err(CpvmLinkerError::Io(e))),
+        }
+    }
+
+    /// Whether `line` starts (after leading whitespace) with the configured
+    /// comment prefix.
+    fn is_comment(&self, line: &str) -> bool {
+        self.options
+            .comment_prefix
+            .as_deref()
+            .is_some_and(|prefix| !prefix.is_empty() && line.trim_start().starts_with(prefix))
+    }
+
+    /// Next line that is neither blank nor a comment, with any BOM removed
+    /// from the first physical line.
+    fn next_content_line(&mut self) -> Option<Result<String, CpvmLinkerError>> {
+        self.next_content_line_observed(None)
+    }
+
+    /// [`next_content_line`](Self::next_content_line), reporting each skipped
+    /// comment line to `observer`.
+    fn next_content_line_observed(
+        &mut self,
+        observer: Option<&dyn LoaderObserver>,
+    ) -> Option<Result<String, CpvmLinkerError>> {
+        loop {
+            let line = match self.next_raw_line()? {
+                Ok(line) => line,
+                Err(e) => return Some(Err(e)),
+            };
+            let line = if self.line_no == 0 {
+                strip_bom(&line).to_string()
+            } else {
+                line
+            };
+            self.line_no += 1;
+            if line.trim().is_empty() {
+                continue;
+            }
+            if self.is_comment(&line) {
+                if let Some(observer) = observer {
+                    observer.on_comment_line(self.line_no, &line);
+                }
+                continue;
+            }
+            return Some(Ok(line));
+        }
+    }
+
+    /// Parse the next data row, reporting comments skipped on the way and
+    /// anomalies in the parsed node to `observer`.
+    fn next_node(
+        &mut self,
+        observer: Option<&dyn LoaderObserver>,
+    ) -> Option<Result<CpvmNodeMeta, CpvmLinkerError>> {
+        self.header.as_ref()?;
+        let line = match self.next_content_line_observed(observer)? {
+            Ok(line) => line,
+            Err(e) => return Some(Err(e)),
+        };
+        let header = self.header.as_ref()?;
+        let fields = split_line_with(&line, self.options.delimiter, self.options.trim_fields);
+        let node = parse_node_row(header, &fields, self.line_no, &self.options);
+        if let (Some(observer), Ok(node)) = (observer, &node) {
+            report_node_anomalies(observer, node);
+        }
+        Some(node)
+    }
+}
+
+impl<R: BufRead> Iterator for CpvmNodeReader<R> {
+    type Item = Result<CpvmNodeMeta, CpvmLinkerError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_node(None)
+    }
+}
+
+/// Resume point in a shard file: bytes and physical lines consumed so far.
+///
+/// `ShardCheckpoint::default()` is the start of the file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ShardCheckpoint {
+    pub byte_offset: u64,
+    pub line_number: usize,
+}
+
+/// Resume loading a shard file at `byte_offset`, parsing to EOF.
+///
+/// Returns the nodes read and the byte offset reached, for checkpointing.
+/// The header is read from the start of the file; an offset inside the
+/// header resumes at the first data row. `byte_offset` must land on a line
+/// boundary, e.g. a value previously returned by this function or by
+/// [`CpvmNodeReader::byte_offset`]. Line numbers in errors count from the
+/// header row; use [`load_cpvm_nodes_from_checkpoint`] to keep physical line
+/// numbers across a resume. Compressed shards cannot be resumed, since a gzip
+/// stream cannot seek.
+pub fn load_cpvm_nodes_from_offset(
+    path: &str,
+    byte_offset: u64,
+) -> Result<(Vec<CpvmNodeMeta>, u64), CpvmLinkerError> {
+    let checkpoint = ShardCheckpoint {
+        byte_offset,
+        line_number: 0,
+    };
+    let (nodes, end) = load_cpvm_nodes_from_checkpoint(path, checkpoint)?;
+    Ok((nodes, end.byte_offset))
+}
+
+/// [`load_cpvm_nodes_from_offset`] resuming at a [`ShardCheckpoint`].
+///
+/// Returns the nodes read and the checkpoint reached. The checkpoint must
+/// come from this function or from [`CpvmNodeReader::checkpoint`], so that
+/// `byte_offset` lands on a line boundary and error line numbers stay
+/// physical line numbers.
+pub fn load_cpvm_nodes_from_checkpoint(
+    path: &str,
+    checkpoint: ShardCheckpoint,
+) -> Result<(Vec<CpvmNodeMeta>, ShardCheckpoint), CpvmLinkerError> {
+    let mut reader = CpvmNodeReader::open(path)?;
+    if reader.header.is_some() && checkpoint.byte_offset > reader.byte_offset {
+        reader
+            .reader
+            .seek(SeekFrom::Start(checkpoint.byte_offset))?;
+        reader.byte_offset = checkpoint.byte_offset;
+        reader.line_no = checkpoint.line_number.max(reader.line_no);
+    }
+    let nodes = reader.by_ref().collect::<Result<Vec<_>, _>>()?;
+    Ok((nodes, reader.checkpoint()))
+}
+
+/// Parse CPVM–EcoNet shard CSV from any buffered source.
+///
+/// Fields are looked up by header column name, so column order is free and
+/// unknown extra columns are ignored. This collects a [`CpvmNodeReader`];
+/// iterate the reader directly to avoid holding every node in memory.
+pub fn load_cpvm_nodes_from_reader<R: BufRead>(
+    reader: R,
+) -> Result<Vec<CpvmNodeMeta>, CpvmLinkerError> {
+    CpvmNodeReader::from_reader(reader)?.collect()
+}
+
+/// Load CPVM–EcoNet qpudatashard CSV into structured node metadata.
+pub fn load_cpvm_nodes_from_csv(path: &str) -> Result<Vec<CpvmNodeMeta>, CpvmLinkerError> {
+    load_cpvm_nodes_with_options(path, LoaderOptions::default())
+}
+
+/// Load a delimited shard (comma, tab, semicolon, …) with explicit options.
+///
+/// With the `gzip` feature, files named `*.gz` or starting with the gzip
+/// magic bytes are decompressed transparently.
+pub fn load_cpvm_nodes_with_options(
+    path: &str,
+    options: LoaderOptions,
+) -> Result<Vec<CpvmNodeMeta>, CpvmLinkerError> {
+    CpvmNodeReader::with_options(open_shard(path)?, options)?.collect()
+}
+
+/// Open a shard file for reading; with the `gzip` feature, compressed input
+/// is decompressed transparently.
+///
+/// Every path-based loader goes through here except the seeking
+/// [`load_cpvm_nodes_from_offset`] and [`CpvmNodeReader::open`].
+fn open_shard(path: &str) -> Result<Box<dyn BufRead>, CpvmLinkerError> {
+    #[cfg(feature = "gzip")]
+    {
+        let mut reader = BufReader::new(File::open(path)?);
+        if is_gzip(path, &mut reader)? {
+            let decoded = BufReader::new(flate2::read::GzDecoder::new(reader));
+            return Ok(Box::new(decoded));
+        }
+        Ok(Box::new(reader))
+    }
+    #[cfg(not(feature = "gzip"))]
+    Ok(Box::new(BufReader::new(File::open(path)?)))
+}
+
+/// Detect gzip input by a `.gz` extension or the `1f 8b` magic prefix.
+#[cfg(feature = "gzip")]
+fn is_gzip<R: BufRead>(path: &str, reader: &mut R) -> std::io::Result<bool> {
+    Ok(path.ends_with(".gz") || reader.fill_buf()?.starts_with(&[0x1f, 0x8b]))
+}
+
+/// Result of [`load_cpvm_nodes_detailed`], separating "no data" from "no file
+/// content".
+#[derive(Debug)]
+pub struct LoadOutcome {
+    pub nodes: Vec<CpvmNodeMeta>,
+    /// Whether a header row was found; false for an empty (or all-comment) file.
+    pub had_header: bool,
+    /// Non-blank, non-comment rows read after the header, including rows
+    /// that failed to parse.
+    pub data_rows_seen: usize,
+    /// `(line_number, error)` for each data row that failed to parse, as in
+    /// [`load_cpvm_nodes_collect_errors`].
+    pub errors: Vec<(usize, CpvmLinkerError)>,
+}
+
+/// Load a shard and report whether it had a header and how many data rows
+/// it held, so an empty file can be told apart from a header-only one and a
+/// shard of malformed rows from one with no rows.
+///
+/// Rows that fail to parse are left out of `nodes` and kept with their line
+/// numbers and errors in [`LoadOutcome::errors`], so `data_rows_seen` counts
+/// both; header and I/O errors still fail the load.
+pub fn load_cpvm_nodes_detailed(path: &str) -> Result<LoadOutcome, CpvmLinkerError> {
+    let mut reader = CpvmNodeReader::with_options(open_shard(path)?, LoaderOptions::default())?;
+    let had_header = reader.header.is_some();
+    let mut nodes = Vec::new();
+    let mut errors = Vec::new();
+    let mut data_rows_seen = 0;
+    while let Some(row) = reader.next() {
+        match row {
+            Ok(node) => nodes.push(node),
+            Err(e) if e.kind() == CpvmErrorKind::Io => return Err(e),
+            Err(e) => errors.push((reader.line_number(), e)),
+        }
+        data_rows_seen += 1;
+    }
+    Ok(LoadOutcome {
+        nodes,
+        had_header,
+        data_rows_seen,
+        errors,
+    })
+}
+
+/// Quick size summary of a shard from [`estimate_shard`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShardEstimate {
+    /// Data rows after the header, excluding blank and comment lines.
+    pub row_count: usize,
+    /// Sum of `q_avg` in m3/s; rows with an unknown flow unit contribute 0.
+    pub total_flow: f64,
+    /// Distinct `region` values.
+    pub region_count: usize,
+}
+
+/// Stream a shard and estimate its size without building node metadata.
+///
+/// Only `q_avg`, `q_unit`, and `region` are read from each row, so this is
+/// much cheaper than a full load; a malformed `q_avg` is still an error.
+pub fn estimate_shard(path: &str) -> Result<ShardEstimate, CpvmLinkerError> {
+    let mut reader = CpvmNodeReader::with_options(open_shard(path)?, LoaderOptions::default())?;
+    let mut estimate = ShardEstimate {
+        row_count: 0,
+        total_flow: 0.0,
+        region_count: 0,
+    };
+    let Some(header) = reader.header.take() else {
+        return Ok(estimate);
+    };
+    let mut regions = HashSet::new();
+    while let Some(line) = reader.next_content_line() {
+        let fields = split_line_with(&line?, reader.options.delimiter, reader.options.trim_fields);
+        let q_avg = parse_f64_field(&header, &fields, "q_avg", reader.line_no, &reader.options)?;
+        let q_unit = FlowUnit::from_str(header.field(&fields, "q_unit"));
+        estimate.row_count += 1;
+        estimate.total_flow += q_avg * q_unit.to_m3_per_s_factor().unwrap_or(0.0);
+        if !regions.contains(header.field(&fields, "region")) {
+            regions.insert(header.field(&fields, "region").to_string());
+        }
+    }
+    estimate.region_count = regions.len();
+    Ok(estimate)
+}
+
+/// Column names from a shard's header row, without parsing any data rows or
+/// requiring the mandatory columns to be present.
+///
+/// Blank and `#` comment lines before the header are skipped; an empty file
+/// yields no columns.
+pub fn inspect_shard_columns(path: &str) -> Result<Vec<String>, CpvmLinkerError> {
+    let mut reader = CpvmNodeReader::with_header(open_shard(path)?, None, LoaderOptions::default());
+    match reader.next_content_line() {
+        Some(line) => Ok(split_csv_line(&line?)),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Load a shard, keeping every good node and every row-level failure.
+///
+/// Failures are `(line_number, error)` pairs using the same 1-based physical
+/// line numbering as the loader's error messages, so they line up with a
+/// spreadsheet viewer. A file that cannot be opened is reported as line 0.
+/// A header error, or an I/O error while reading, ends the load; the I/O
+/// error is reported at the line that could not be read.
+pub fn load_cpvm_nodes_collect_errors(
+    path: &str,
+) -> (Vec<CpvmNodeMeta>, Vec<(usize, CpvmLinkerError)>) {
+    let file = match open_shard(path) {
+        Ok(file) => file,
+        Err(e) => return (Vec::new(), vec![(0, e)]),
+    };
+    let mut reader = CpvmNodeReader::with_header(file, None, LoaderOptions::default());
+    if let Err(e) = reader.read_header(None) {
+        let line = match e.kind() {
+            CpvmErrorKind::Io => reader.line_number() + 1,
+            CpvmErrorKind::Parse => e.line().unwrap_or(reader.line_number()),
+        };
+        return (Vec::new(), vec![(line, e)]);
+    }
+
+    let mut nodes = Vec::new();
+    let mut errors = Vec::new();
+    while let Some(row) = reader.next() {
+        match row {
+            Ok(node) => nodes.push(node),
+            Err(e) if e.kind() == CpvmErrorKind::Io => {
+                errors.push((reader.line_number() + 1, e));
+                break;
+            }
+            Err(e) => errors.push((reader.line_number(), e)),
+        }
+    }
+    (nodes, errors)
+}
+
+/// Report each `NodeId` that occurs more than once, with its occurrence count,
+/// in order of first appearance.
+pub fn find_duplicate_node_ids(nodes: &[CpvmNodeMeta]) -> Vec<(NodeId, usize)> {
+    let mut counts: HashMap<&NodeId, usize> = HashMap::new();
+    for node in nodes {
+        *counts.entry(&node.node_id).or_insert(0) += 1;
+    }
+    let mut duplicates = Vec::new();
+    for node in nodes {
+        if let Some(count) = counts.remove(&node.node_id) {
+            if count > 1 {
+                duplicates.push((node.node_id.clone(), count));
+            }
+        }
+    }
+    duplicates
+}
+
+/// Callbacks for anomalies the loader otherwise handles silently.
+///
+/// All methods default to no-ops, so implementors override only what they
+/// log.
+pub trait LoaderObserver {
+    /// `ecoimpactscore` lies outside `[0, 1]` and will be clamped on evaluation.
+    fn on_clamped_score(&self, _node: &NodeId, _raw: f64) {}
+    /// A `cin_unit`, `q_unit` or `sec_unit` token fell back to `Other`.
+    fn on_unknown_unit(&self, _node: &NodeId, _raw: &str) {}
+    /// A comment line (1-based physical `line_no`) was skipped.
+    fn on_comment_line(&self, _line_no: usize, _line: &str) {}
+}
+
+/// Call `observer`'s per-node hooks for anomalies in a parsed row.
+fn report_node_anomalies(observer: &dyn LoaderObserver, node: &CpvmNodeMeta) {
+    if !(0.0..=1.0).contains(&node.ecoimpactscore) {
+        observer.on_clamped_score(&node.node_id, node.ecoimpactscore);
+    }
+    if let ConcentrationUnit::Other(raw) = &node.cin_unit {
+        observer.on_unknown_unit(&node.node_id, raw);
+    }
+    if let FlowUnit::Other(raw) = &node.q_unit {
+        observer.on_unknown_unit(&node.node_id, raw);
+    }
+    if let Some(SecondaryContaminant {
+        cin_unit: ConcentrationUnit::Other(raw),
+        ..
+    }) = &node.secondary
+    {
+        observer.on_unknown_unit(&node.node_id, raw);
+    }
+}
+
+/// [`load_cpvm_nodes_from_csv`] reporting anomalies to `observer` as each
+/// row streams in; `None` behaves exactly like the plain loader.
+pub fn load_cpvm_nodes_observed(
+    path: &str,
+    observer: Option<&dyn LoaderObserver>,
+) -> Result<Vec<CpvmNodeMeta>, CpvmLinkerError> {
+    let mut reader = CpvmNodeReader::with_header(open_shard(path)?, None, LoaderOptions::default());
+    reader.read_header(observer)?;
+    let mut nodes = Vec::new();
+    while let Some(node) = reader.next_node(observer) {
+        nodes.push(node?);
+    }
+    Ok(nodes)
+}
+
+/// Load a shard, rejecting it with `CpvmLinkerError::Parse` if any `NodeId`
+/// appears on more than one row (which would double-count Karma).
+pub fn load_cpvm_nodes_from_csv_unique(path: &str) -> Result<Vec<CpvmNodeMeta>, CpvmLinkerError> {
+    let nodes = load_cpvm_nodes_from_csv(path)?;
+    let duplicates = find_duplicate_node_ids(&nodes);
+    if !duplicates.is_empty() {
+        let listed: Vec<String> = duplicates
+            .iter()
+            .map(|(id, count)| format!("{} (x{})", id.0, count))
+            .collect();
+        return Err(CpvmLinkerError::Parse(format!(
+            "Shard {} has duplicate node_id rows: {}",
+            path,
+            listed.join(", ")
+        )));
+    }
+    Ok(nodes)
+}
+
+/// How [`merge_node_sets`] resolves a `NodeId` seen in more than one row.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    /// Keep the first occurrence and drop later ones.
+    KeepFirst,
+    /// Replace earlier occurrences with the last one, at the first one's position.
+    KeepLast,
+    /// Fail with a `Parse` error naming the conflicting `NodeId`.
+    Error,
+    /// Keep the first occurrence when later ones are
+    /// [`CpvmNodeMeta::physically_equal`] to it (e.g. the same baseline in
+    /// ng/L and mg/L); otherwise fail as [`ConflictPolicy::Error`].
+    ErrorUnlessPhysicallyEqual,
+}
+
+/// Merge node sets from several shards of the same basin, keyed by `NodeId`.
+///
+/// Output follows first-appearance order across `sets`.
+pub fn merge_node_sets(
+    sets: Vec<Vec<CpvmNodeMeta>>,
+    on_conflict: ConflictPolicy,
+) -> Result<Vec<CpvmNodeMeta>, CpvmLinkerError> {
+    let mut merged: Vec<CpvmNodeMeta> = Vec::new();
+    let mut positions: HashMap<NodeId, usize> = HashMap::new();
+    for node in sets.into_iter().flatten() {
+        match positions.get(&node.node_id) {
+            None => {
+                positions.insert(node.node_id.clone(), merged.len());
+                merged.push(node);
+            }
+            Some(&idx) => match on_conflict {
+                ConflictPolicy::KeepFirst => {}
+                ConflictPolicy::KeepLast => merged[idx] = node,
+                ConflictPolicy::Error => return Err(duplicate_node_error(&node.node_id)),
+                ConflictPolicy::ErrorUnlessPhysicallyEqual => {
+                    if !merged[idx].physically_equal(&node) {
+                        return Err(duplicate_node_error(&node.node_id));
+                    }
+                }
+            },
+        }
+    }
+    Ok(merged)
+}
+
+/// Check node metadata for values that would silently produce nonsense Karma.
+///
+/// Returns a `Parse` error naming the first offending field and the node.
+pub fn validate_node_meta(meta: &CpvmNodeMeta) -> Result<(), CpvmLinkerError> {
+    let invalid = |field: &str, value: f64, rule: &str| {
+        Err(CpvmLinkerError::Parse(format!(
+            "Node {}: {} = {} must be {}",
+            meta.node_id.0, field, value, rule
+        )))
+    };
+    if meta.q_avg.is_nan() || meta.q_avg < 0.0 {
+        return invalid("q_avg", meta.q_avg, ">= 0");
+    }
+    if meta.horizon_s.is_nan() || meta.horizon_s <= 0.0 {
+        return invalid("horizon_s", meta.horizon_s, "> 0");
+    }
+    if !(0.0..=1.0).contains(&meta.ecoimpactscore) {
+        return invalid("ecoimpactscore", meta.ecoimpactscore, "in [0, 1]");
+    }
+    if meta.cin_baseline.is_nan() || meta.cin_baseline < 0.0 {
+        return invalid("cin_baseline", meta.cin_baseline, ">= 0");
+    }
+    if !meta.karma_per_unit.is_finite() {
+        return invalid("karma_per_unit", meta.karma_per_unit, "finite");
+    }
+    if meta.karma_per_unit < 0.0 {
+        return invalid("karma_per_unit", meta.karma_per_unit, ">= 0");
+    }
+    Ok(())
+}
+
+/// Non-fatal data-quality issue found by [`load_cpvm_nodes_with_warnings`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Warning {
+    /// `karma_per_unit` was negative (likely a sign error) and was clamped to 0.
+    NegativeKarmaPerUnit(NodeId),
+}
+
+/// Lenient load: negative `karma_per_unit` values are clamped to 0 and
+/// reported as [`Warning`]s instead of failing the shard.
+///
+/// Row parse errors still fail the load.
+pub fn load_cpvm_nodes_with_warnings(
+    path: &str,
+) -> Result<(Vec<CpvmNodeMeta>, Vec<Warning>), CpvmLinkerError> {
+    let mut nodes = load_cpvm_nodes_from_csv(path)?;
+    let mut warnings = Vec::new();
+    for node in &mut nodes {
+        if node.karma_per_unit < 0.0 {
+            node.karma_per_unit = 0.0;
+            warnings.push(Warning::NegativeKarmaPerUnit(node.node_id.clone()));
+        }
+    }
+    Ok((nodes, warnings))
+}
+
+/// Load a shard and run [`validate_node_meta`] on every node.
+///
+/// All validation failures are reported together in a single `Parse` error
+/// rather than stopping at the first bad node.
+pub fn load_cpvm_nodes_from_csv_validated(
+    path: &str,
+) -> Result<Vec<CpvmNodeMeta>, CpvmLinkerError> {
+    let nodes = load_cpvm_nodes_from_csv(path)?;
+    let failures: Vec<String> = nodes
+        .iter()
+        .filter_map(|meta| match validate_node_meta(meta) {
+            Err(CpvmLinkerError::Parse(msg)) => Some(msg),
+            Err(other) => Some(other.to_string()),
+            Ok(()) => None,
+        })
+        .collect();
+    if !failures.is_empty() {
+        return Err(CpvmLinkerError::Parse(format!(
+            "{} invalid node(s): {}",
+            failures.len(),
+            failures.join("; ")
+        )));
+    }
+    Ok(nodes)
+}
+
+/// Chainable builder for [`CpvmNodeMeta`].
+///
+/// `node_id`, `asset_type`, `cin_unit`, `q_unit`, and `horizon_s` must be set;
+/// text fields default to empty and numeric fields to 0.0. `build` runs
+/// [`validate_node_meta`] on the result.
+#[derive(Debug, Clone, Default)]
+pub struct CpvmNodeMetaBuilder {
+    node_id: Option<NodeId>,
+    asset_type: Option<AssetType>,
+    waterbody: String,
+    region: String,
+    cpvm_profile: String,
+    cin_baseline: f64,
+    cin_unit: Option<ConcentrationUnit>,
+    q_avg: f64,
+    q_unit: Option<FlowUnit>,
+    horizon_s: Option<f64>,
+    ecoimpactscore: f64,
+    karma_per_unit: f64,
+    notes: String,
+    tags: Vec<String>,
+    secondary: Option<SecondaryContaminant>,
+    residence_time_s: Option<f64>,
+    lat: Option<f64>,
+    lon: Option<f64>,
+}
+
+impl CpvmNodeMeta {
+    pub fn builder() -> CpvmNodeMetaBuilder {
+        CpvmNodeMetaBuilder::default()
+    }
+}
+
+impl CpvmNodeMetaBuilder {
+    pub fn node_id(mut self, node_id: impl Into<String>) -> Self {
+        self.node_id = Some(NodeId(node_id.into()));
+        self
+    }
+
+    pub fn asset_type(mut self, asset_type: AssetType) -> Self {
+        self.asset_type = Some(asset_type);
+        self
+    }
+
+    pub fn waterbody(mut self, waterbody: impl Into<String>) -> Self {
+        self.waterbody = waterbody.into();
+        self
+    }
+
+    pub fn region(mut self, region: impl Into<String>) -> Self {
+        self.region = region.into();
+        self
+    }
+
+    pub fn cpvm_profile(mut self, cpvm_profile: impl Into<String>) -> Self {
+        self.cpvm_profile = cpvm_profile.into();
+        self
+    }
+
+    pub fn cin_baseline(mut self, cin_baseline: f64) -> Self {
+        self.cin_baseline = cin_baseline;
+        self
+    }
+
+    pub fn cin_unit(mut self, cin_unit: ConcentrationUnit) -> Self {
+        self.cin_unit = Some(cin_unit);
+        self
+    }
+
+    pub fn q_avg(mut self, q_avg: f64) -> Self {
+        self.q_avg = q_avg;
+        self
+    }
+
+    pub fn q_unit(mut self, q_unit: FlowUnit) -> Self {
+        self.q_unit = Some(q_unit);
+        self
+    }
+
+    pub fn horizon_s(mut self, horizon_s: f64) -> Self {
+        self.horizon_s = Some(horizon_s);
+        self
+    }
+
+    pub fn ecoimpactscore(mut self, ecoimpactscore: f64) -> Self {
+        self.ecoimpactscore = ecoimpactscore;
+        self
+    }
+
+    pub fn karma_per_unit(mut self, karma_per_unit: f64) -> Self {
+        self.karma_per_unit = karma_per_unit;
+        self
+    }
+
+    pub fn notes(mut self, notes: impl Into<String>) -> Self {
+        self.notes = notes.into();
+        self
+    }
+
+    /// Append one governance tag.
+    pub fn tag(mut self, tag: impl Into<String>) -> Self {
+        self.tags.push(tag.into());
+        self
+    }
+
+    pub fn secondary(mut self, secondary: SecondaryContaminant) -> Self {
+        self.secondary = Some(secondary);
+        self
+    }
+
+    pub fn residence_time_s(mut self, residence_time_s: f64) -> Self {
+        self.residence_time_s = Some(residence_time_s);
+        self
+    }
+
+    /// Set WGS84 latitude and longitude in degrees.
+    pub fn coordinates(mut self, lat: f64, lon: f64) -> Self {
+        self.lat = Some(lat);
+        self.lon = Some(lon);
+        self
+    }
+
+    /// Assemble and validate the node, erroring on any unset required field.
+    pub fn build(self) -> Result<CpvmNodeMeta, CpvmLinkerError> {
+        fn required<T>(value: Option<T>, field: &str) -> Result<T, CpvmLinkerError> {
+            value.ok_or_else(|| {
+                CpvmLinkerError::Parse(format!("CpvmNodeMetaBuilder: {} was never set", field))
+            })
+        }
+
+        let meta = CpvmNodeMeta {
+            node_id: required(self.node_id, "node_id")?,
+            asset_type: required(self.asset_type, "asset_type")?,
+            waterbody: Waterbody(self.waterbody),
+            region: Region(self.region),
+            cpvm_profile: self.cpvm_profile,
+            cin_baseline: self.cin_baseline,
+            cin_unit: required(self.cin_unit, "cin_unit")?,
+            q_avg: self.q_avg,
+            q_unit: required(self.q_unit, "q_unit")?,
+            horizon_s: required(self.horizon_s, "horizon_s")?,
+            ecoimpactscore: self.ecoimpactscore,
+            karma_per_unit: self.karma_per_unit,
+            notes: self.notes,
+            tags: self.tags,
+            secondary: self.secondary,
+            residence_time_s: self.residence_time_s,
+            lat: self.lat,
+            lon: self.lon,
+        };
+        validate_node_meta(&meta)?;
+        Ok(meta)
+    }
+}
+
+/// Quote a CSV field when it contains a delimiter, quote, or line break,
+/// doubling any embedded quotes.
+fn quote_csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Write nodes as a CPVM–EcoNet shard CSV with the canonical columns plus
+/// `tags`, the secondary-contaminant columns, `residence_time_s`, `lat`,
+/// `lon`, and `notes`.
+///
+/// Output round-trips through [`load_cpvm_nodes_from_reader`] unchanged, as
+/// long as no tag contains `|`.
+pub fn write_cpvm_nodes_to_csv<W: Write>(
+    mut writer: W,
+    nodes: &[CpvmNodeMeta],
+) -> Result<(), CpvmLinkerError> {
+    writeln!(
+        writer,
+        "{},tags,sec_name,sec_cin,sec_unit,residence_time_s,lat,lon,notes",
+        REQUIRED_COLUMNS.join(",")
+    )?;
+    for node in nodes {
+        let (sec_name, sec_cin, sec_unit) = match &node.secondary {
+            Some(sec) => (
+                quote_csv_field(&sec.name),
+                sec.cin_baseline.to_string(),
+                quote_csv_field(sec.cin_unit.as_canonical_str()),
+            ),
+            None => (String::new(), String::new(), String::new()),
+        };
+        let row = [
+            quote_csv_field(&node.node_id.0),
+            quote_csv_field(node.asset_type.as_canonical_str()),
+            quote_csv_field(&node.waterbody.0),
+            quote_csv_field(&node.region.0),
+            quote_csv_field(&node.cpvm_profile),
+            node.cin_baseline.to_string(),
+            quote_csv_field(node.cin_unit.as_canonical_str()),
+            node.q_avg.to_string(),
+            quote_csv_field(node.q_unit.as_canonical_str()),
+            node.horizon_s.to_string(),
+            node.ecoimpactscore.to_string(),
+            node.karma_per_unit.to_string(),
+            quote_csv_field(&node.tags.join("|")),
+            sec_name,
+            sec_cin,
+            sec_unit,
+            node.residence_time_s
+                .map(|t| t.to_string())
+                .unwrap_or_default(),
+            node.lat.map(|v| v.to_string()).unwrap_or_default(),
+            node.lon.map(|v| v.to_string()).unwrap_or_default(),
+            quote_csv_field(&node.notes),
+        ];
+        writeln!(writer, "{}", row.join(","))?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Default Karma-per-unit used when a shard leaves `karma_per_unit` unset.
+///
+/// Values follow the scale of the Phoenix 2026 CPVM shard: engineered and
+/// storage assets rate highest, flowing reaches and clusters moderate.
+pub fn default_karma_per_unit(asset: &AssetType) -> f64 {
+    match asset {
+        AssetType::Reservoir => 1.0e6,
+        AssetType::Plant => 1.0e6,
+        AssetType::Basin => 6.7e5,
+        AssetType::RiverReach => 5.0e5,
+        AssetType::WatershedCluster => 5.0e5,
+        AssetType::Other(_) => 1.0,
+    }
+}
+
+impl CpvmNodeMeta {
+    /// Replace a zero or non-finite `karma_per_unit` with `defaults(asset_type)`.
+    ///
+    /// Pass `&default_karma_per_unit` for the built-in table, or any closure
+    /// to override it. Valid values are left untouched.
+    pub fn with_karma_fallback(&mut self, defaults: &dyn Fn(&AssetType) -> f64) {
+        if self.karma_per_unit == 0.0 || !self.karma_per_unit.is_finite() {
+            self.karma_per_unit = defaults(&self.asset_type);
+        }
+    }
+
+    /// Whether two rows describe the same physical node, comparing the
+    /// baseline in mg/L and the flow in m3/s within a relative tolerance of
+    /// 1e-9.
+    ///
+    /// Units that do not convert (e.g. MPN/100mL) fall back to exact unit
+    /// and value equality. `node_id`, `asset_type`, `horizon_s`,
+    /// `ecoimpactscore`, and `karma_per_unit` must match exactly; descriptive
+    /// fields such as `notes` and `tags` are ignored.
+    pub fn physically_equal(&self, other: &Self) -> bool {
+        fn close(a: f64, b: f64) -> bool {
+            a == b || (a - b).abs() <= 1e-9 * a.abs().max(b.abs())
+        }
+        let conc_equal = match (
+            self.cin_unit.to_mg_per_l_factor(),
+            other.cin_unit.to_mg_per_l_factor(),
+        ) {
+            (Some(fa), Some(fb)) => close(self.cin_baseline * fa, other.cin_baseline * fb),
+            _ => self.cin_unit == other.cin_unit && self.cin_baseline == other.cin_baseline,
+        };
+        let flow_equal = match (
+            self.q_unit.to_m3_per_s_factor(),
+            other.q_unit.to_m3_per_s_factor(),
+        ) {
+            (Some(fa), Some(fb)) => close(self.q_avg * fa, other.q_avg * fb),
+            _ => self.q_unit == other.q_unit && self.q_avg == other.q_avg,
+        };
+        conc_equal
+            && flow_equal
+            && self.node_id == other.node_id
+            && self.asset_type == other.asset_type
+            && self.horizon_s == other.horizon_s
+            && self.ecoimpactscore == other.ecoimpactscore
+            && self.karma_per_unit == other.karma_per_unit
+    }
+}
+
+/// Rule deriving a node's `safe_threshold` from its metadata and `cref`.
+pub trait ThresholdPolicy {
+    fn threshold(&self, meta: &CpvmNodeMeta, cref: f64) -> f64;
+}
+
+/// Default policy: `min(cin_baseline, cref)`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MinBaselineCref;
+
+impl ThresholdPolicy for MinBaselineCref {
+    fn threshold(&self, meta: &CpvmNodeMeta, cref: f64) -> f64 {
+        // In a full stack, this would be min(EPA, EU, WHO, etc.) and possibly
+        // lower than baseline.
+        meta.cin_baseline.min(cref)
+    }
+}
+
+/// Policy setting the threshold to a fixed fraction of `cin_baseline`.
+#[derive(Debug, Clone, Copy)]
+pub struct FixedFraction(pub f64);
+
+impl ThresholdPolicy for FixedFraction {
+    fn threshold(&self, meta: &CpvmNodeMeta, _cref: f64) -> f64 {
+        meta.cin_baseline * self.0
+    }
+}
+
+/// The `p`-th percentile (0-100) of `cin_baseline` across `nodes`.
+///
+/// Uses linear interpolation between closest ranks; `p` is clamped into
+/// range and NaN baselines are ignored. Returns `None` when no baselines
+/// remain or `p` is NaN.
+pub fn percentile_threshold(nodes: &[CpvmNodeMeta], p: f64) -> Option<f64> {
+    let mut values: Vec<f64> = nodes
+        .iter()
+        .map(|n| n.cin_baseline)
+        .filter(|v| !v.is_nan())
+        .collect();
+    if values.is_empty() || p.is_nan() {
+        return None;
+    }
+    values.sort_by(f64::total_cmp);
+    let rank = p.clamp(0.0, 100.0) / 100.0 * (values.len() - 1) as f64;
+    let lo = rank.floor() as usize;
+    let hi = rank.ceil() as usize;
+    Some(values[lo] + (values[hi] - values[lo]) * (rank - lo as f64))
+}
+
+/// Policy applying one precomputed basin-wide threshold to every node.
+#[derive(Debug, Clone, Copy)]
+pub struct BasinPercentile(pub f64);
+
+impl BasinPercentile {
+    /// Precompute the `p`-th percentile of `nodes`; see [`percentile_threshold`].
+    pub fn from_nodes(nodes: &[CpvmNodeMeta], p: f64) -> Option<Self> {
+        percentile_threshold(nodes, p).map(BasinPercentile)
+    }
+}
+
+impl ThresholdPolicy for BasinPercentile {
+    fn threshold(&self, _meta: &CpvmNodeMeta, _cref: f64) -> f64 {
+        self.0
+    }
+}
+
+/// Construct a node-specific CPVM safety config from domain rules.
+///
+/// This function is intentionally simple and deterministic so that higher-level
+/// governance logic can override or wrap it as needed. Uses [`MinBaselineCref`];
+/// see [`derive_safety_config_with_policy`].
+pub fn derive_safety_config(
+    meta: &CpvmNodeMeta,
+    cref_default: f64,
+    lambda_clf: f64,
+    mu_cbf: f64,
+) -> CpvmSafetyConfig {
+    derive_safety_config_with_policy(meta, cref_default, lambda_clf, mu_cbf, &MinBaselineCref)
+}
+
+/// [`derive_safety_config`] with `safe_threshold` supplied by `policy`.
+pub fn derive_safety_config_with_policy(
+    meta: &CpvmNodeMeta,
+    cref_default: f64,
+    lambda_clf: f64,
+    mu_cbf: f64,
+    policy: &dyn ThresholdPolicy,
+) -> CpvmSafetyConfig {
+    let safe_threshold = policy.threshold(meta, cref_default);
+
+    CpvmSafetyConfig {
+        safe_threshold,
+        cref: cref_default,
+        lambda_clf,
+        mu_cbf,
+    }
+}
+
+/// Regulatory concentration limits for one CPVM profile, in the profile's
+/// concentration unit. Absent agencies are `None`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ProfileLimits {
+    pub epa: Option<f64>,
+    pub eu: Option<f64>,
+    pub who: Option<f64>,
+}
+
+impl ProfileLimits {
+    /// Tightest of the present limits, if any.
+    pub fn binding_limit(&self) -> Option<f64> {
+        [self.epa, self.eu, self.who]
+            .into_iter()
+            .flatten()
+            .reduce(f64::min)
+    }
+}
+
+/// Regulatory limits keyed by CPVM profile name.
+#[derive(Debug, Clone, Default)]
+pub struct RegulatoryLimits {
+    pub by_profile: HashMap<String, ProfileLimits>,
+}
+
+/// Derive a safety config whose threshold is min(EPA, EU, WHO) for the
+/// node's profile.
+///
+/// Missing agencies are ignored; if the profile has no limits at all the
+/// threshold falls back to the node's baseline. C_ref is set to the same
+/// threshold so that residuals are normalized by the binding limit.
+pub fn derive_safety_config_regulatory(
+    meta: &CpvmNodeMeta,
+    limits: &RegulatoryLimits,
+    lambda_clf: f64,
+    mu_cbf: f64,
+) -> CpvmSafetyConfig {
+    let safe_threshold = limits
+        .by_profile
+        .get(&meta.cpvm_profile)
+        .and_then(ProfileLimits::binding_limit)
+        .unwrap_or(meta.cin_baseline);
+
+    CpvmSafetyConfig {
+        safe_threshold,
+        cref: safe_threshold,
+        lambda_clf,
+        mu_cbf,
+    }
+}
+
+/// Create a fully bound CPVM node configuration from metadata and global defaults.
+///
+/// In the full Cybercore-Brain stack, per-profile C_ref and weights would be
+/// injected from CEIM‑XJ governance logic; this function provides a sane core.
+pub fn bind_cpvm_config(
+    meta: CpvmNodeMeta,
+    cref_default: f64,
+    lambda_clf: f64,
+    mu_cbf: f64,
+) -> CpvmNodeConfig {
+    let safety = derive_safety_config(&meta, cref_default, lambda_clf, mu_cbf);
+    CpvmNodeConfig { meta, safety }
+}
+
+/// Bundle of the global safety parameters passed to [`bind_cpvm_config`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+pub struct SafetyDefaults {
+    pub cref: f64,
+    pub lambda_clf: f64,
+    pub mu_cbf: f64,
+}
+
+/// Bind a node using per-profile safety parameters from governance.
+///
+/// Looks up `overrides` by `meta.cpvm_profile` and falls back to `defaults`
+/// when the profile has no entry.
+pub fn bind_cpvm_config_with_overrides(
+    meta: CpvmNodeMeta,
+    defaults: SafetyDefaults,
+    overrides: &HashMap<String, SafetyDefaults>,
+) -> CpvmNodeConfig {
+    let params = overrides
+        .get(&meta.cpvm_profile)
+        .copied()
+        .unwrap_or(defaults);
+    bind_cpvm_config(meta, params.cref, params.lambda_clf, params.mu_cbf)
+}
+
+/// Deployment safety configuration: global defaults plus per-profile
+/// overrides, as loaded by [`load_safety_defaults_toml`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct SafetyDefaultsFile {
+    pub defaults: SafetyDefaults,
+    /// Fully resolved parameters per `cpvm_profile`.
+    pub profiles: HashMap<String, SafetyDefaults>,
+}
+
+impl SafetyDefaultsFile {
+    /// Bind a node via [`bind_cpvm_config_with_overrides`].
+    pub fn bind(&self, meta: CpvmNodeMeta) -> CpvmNodeConfig {
+        bind_cpvm_config_with_overrides(meta, self.defaults, &self.profiles)
+    }
+}
+
+/// Load a [`SafetyDefaultsFile`] from TOML of the form:
+///
+/// ```toml
+/// [defaults]
+/// cref = 5.0
+/// lambda_clf = 10.0
+/// mu_cbf = 100.0
+///
+/// [profiles.PFAS_STRICT]
+/// cref = 2.0
+/// ```
+///
+/// Profile tables may set any subset of the keys; the rest come from
+/// `[defaults]`.
+#[cfg(feature = "serde")]
+pub fn load_safety_defaults_toml(path: &str) -> Result<SafetyDefaultsFile, CpvmLinkerError> {
+    #[derive(serde::Deserialize)]
+    struct ProfileOverride {
+        cref: Option<f64>,
+        lambda_clf: Option<f64>,
+        mu_cbf: Option<f64>,
+    }
+
+    #[derive(serde::Deserialize)]
+    struct RawFile {
+        defaults: SafetyDefaults,
+        #[serde(default)]
+        profiles: HashMap<String, ProfileOverride>,
+    }
+
+    let text = std::fs::read_to_string(path)?;
+    let raw: RawFile = toml::from_str(&text)
+        .map_err(|e| CpvmLinkerError::Parse(format!("Invalid safety config {}: {}", path, e)))?;
+    let defaults = raw.defaults;
+    let profiles = raw
+        .profiles
+        .into_iter()
+        .map(|(name, o)| {
+            let params = SafetyDefaults {
+                cref: o.cref.unwrap_or(defaults.cref),
+                lambda_clf: o.lambda_clf.unwrap_or(defaults.lambda_clf),
+                mu_cbf: o.mu_cbf.unwrap_or(defaults.mu_cbf),
+            };
+            (name, params)
+        })
+        .collect();
+    Ok(SafetyDefaultsFile { defaults, profiles })
+}
+
+/// Compute mass load avoided M = (C_in - C_out) * Q * t, using consistent units.
+///
+/// This assumes:
+/// - C_in and C_out share the same concentration units.
+/// - Q in m3/s, t in s.
+/// - For Karma accounting, *relative* magnitude matters; absolute unit conversion
+///   is handled at governance level if needed.
+///
+/// Returns 0.0 if any input is NaN or infinite, or if the result would exceed
+/// [`MAX_MASS_AVOIDED`]; see [`try_compute_mass_avoided`].
+pub fn compute_mass_avoided(cin: f64, cout: f64, q_m3_per_s: f64, horizon_s: f64) -> f64 {
+    try_compute_mass_avoided(cin, cout, q_m3_per_s, horizon_s).unwrap_or(0.0)
+}
+
+/// Largest `|mass_avoided|` accepted by [`try_compute_mass_avoided`].
+///
+/// Half of `f64::MAX` leaves headroom for summing results and multiplying by
+/// `ecoimpactscore <= 1` without reaching `inf`; decade-long horizons on basin
+/// flows stay many orders of magnitude below it.
+pub const MAX_MASS_AVOIDED: f64 = f64::MAX / 2.0;
+
+/// Checked variant of [`compute_mass_avoided`].
+///
+/// Returns `CpvmLinkerError::Parse` naming the first non-finite argument, or
+/// reporting an overflow when the product exceeds [`MAX_MASS_AVOIDED`], so a
+/// single corrupt field cannot poison downstream Karma aggregation.
+pub fn try_compute_mass_avoided(
+    cin: f64,
+    cout: f64,
+    q_m3_per_s: f64,
+    horizon_s: f64,
+) -> Result<f64, CpvmLinkerError> {
+    try_compute_mass_avoided_with(cin, cout, q_m3_per_s, horizon_s, false)
+}
+
+/// Unclamped mass balance: negative when `cout > cin`, tracking re-release.
+///
+/// [`evaluate_ecoimpact_for_node`] uses the clamped [`compute_mass_avoided`]
+/// unless [`EvalOptions::signed_mass`] is set. Returns 0.0 for non-finite
+/// inputs.
+pub fn compute_mass_avoided_signed(cin: f64, cout: f64, q_m3_per_s: f64, horizon_s: f64) -> f64 {
+    try_compute_mass_avoided_with(cin, cout, q_m3_per_s, horizon_s, true).unwrap_or(0.0)
+}
+
+fn try_compute_mass_avoided_with(
+    cin: f64,
+    cout: f64,
+    q_m3_per_s: f64,
+    horizon_s: f64,
+    signed: bool,
+) -> Result<f64, CpvmLinkerError> {
+    let args = [
+        ("cin", cin),
+        ("cout", cout),
+        ("q_m3_per_s", q_m3_per_s),
+        ("horizon_s", horizon_s),
+    ];
+    if let Some((name, v)) = args.iter().find(|(_, v)| !v.is_finite()) {
+        return Err(CpvmLinkerError::Parse(format!(
+            "compute_mass_avoided: {} is not finite ({})",
+            name, v
+        )));
+    }
+    let delta_c = if signed {
+        cin - cout
+    } else {
+        (cin - cout).max(0.0)
+    };
+    let mass = delta_c * q_m3_per_s * horizon_s;
+    if !mass.is_finite() || mass.abs() > MAX_MASS_AVOIDED {
+        return Err(CpvmLinkerError::Parse(format!(
+            "compute_mass_avoided: overflow, ({} - {}) * {} * {} exceeds {:e}",
+            cin, cout, q_m3_per_s, horizon_s, MAX_MASS_AVOIDED
+        )));
+    }
+    Ok(mass)
+}
+
+/// Compute mass avoided over a flow hydrograph instead of a constant Q.
+///
+/// `flow_series` holds `(time_s, q_m3_per_s)` samples in time order; the load
+/// `max(C_in - C_out, 0) * Q(t)` is integrated with the trapezoidal rule.
+/// Intervals with non-increasing time are skipped, as in the CEIM core. With
+/// uniform Q this reduces to [`compute_mass_avoided`] over the series span.
+pub fn compute_mass_avoided_series(cin: f64, cout: f64, flow_series: &[(f64, f64)]) -> f64 {
+    let load = |q: f64| (cin - cout).max(0.0) * q;
+    flow_series
+        .windows(2)
+        .map(|w| {
+            let ((t0, q0), (t1, q1)) = (w[0], w[1]);
+            let dt = t1 - t0;
+            if dt <= 0.0 {
+                0.0
+            } else {
+                0.5 * (load(q0) + load(q1)) * dt
+            }
+        })
+        .sum()
+}
+
+/// Compute mass avoided for a time-varying inlet concentration.
+///
+/// `cin_series` holds `(time_s, cin)` samples; `max(C_in(t) - C_out, 0) * Q`
+/// is integrated with the trapezoidal rule, clamping each sample before
+/// averaging. A single sample spans no interval and yields 0.0. Returns
+/// `CpvmLinkerError::Parse` if timestamps decrease (or are NaN).
+pub fn try_compute_mass_avoided_cin_series(
+    cin_series: &[(f64, f64)],
+    cout: f64,
+    q_m3_per_s: f64,
+) -> Result<f64, CpvmLinkerError> {
+    let load = |cin: f64| (cin - cout).max(0.0) * q_m3_per_s;
+    let mut mass = 0.0;
+    for (idx, w) in cin_series.windows(2).enumerate() {
+        let ((t0, c0), (t1, c1)) = (w[0], w[1]);
+        let dt = t1 - t0;
+        if dt.is_nan() || dt < 0.0 {
+            return Err(CpvmLinkerError::Parse(format!(
+                "C_in series timestamps not monotonic at sample {}: {} -> {}",
+                idx + 1,
+                t0,
+                t1
+            )));
+        }
+        mass += 0.5 * (load(c0) + load(c1)) * dt;
+    }
+    Ok(mass)
+}
+
+/// Infallible variant of [`try_compute_mass_avoided_cin_series`]; returns 0.0
+/// for a non-monotonic series.
+pub fn compute_mass_avoided_cin_series(
+    cin_series: &[(f64, f64)],
+    cout: f64,
+    q_m3_per_s: f64,
+) -> f64 {
+    try_compute_mass_avoided_cin_series(cin_series, cout, q_m3_per_s).unwrap_or(0.0)
+}
+
+/// Options controlling how [`try_evaluate_ecoimpact_for_node_with_options`]
+/// accounts for mass and treats out-of-range scores. The default matches
+/// [`evaluate_ecoimpact_for_node`].
+#[derive(Debug, Clone, Default)]
+pub struct EvalOptions {
+    /// Use [`compute_mass_avoided_signed`] so `cout > cin` yields negative
+    /// mass (and Karma) instead of clamping at zero.
+    pub signed_mass: bool,
+    /// Reject an `ecoimpactscore` outside `[0, 1]` instead of clamping it.
+    pub strict_score: bool,
+}
+
+/// Evaluate eco-impact and Karma for a CPVM-controlled node over its horizon.
+///
+/// This function is the core bridge: controllers can propose C_out, and this
+/// returns the resulting mass avoided and Karma gain consistent with CEIM-style
+/// ecoimpactscore and Karma-per-unit configuration.
+///
+/// C_out is taken to be in the node's `cin_unit`. Nodes whose units cannot be
+/// resolved (see [`try_evaluate_ecoimpact_for_node`]) yield zero mass and Karma.
+/// The mass delta is clamped at zero; see [`evaluate_ecoimpact_for_node_with_options`].
+pub fn evaluate_ecoimpact_for_node(cfg: &CpvmNodeConfig, cout: f64) -> EcoImpactResult {
+    evaluate_ecoimpact_for_node_with_options(cfg, cout, &EvalOptions::default())
+}
+
+/// Fallible [`evaluate_ecoimpact_for_node`] with explicit [`EvalOptions`].
+///
+/// C_out is in the node's `cin_unit`. Unlike
+/// [`evaluate_ecoimpact_for_node_with_options`], failures such as a
+/// `strict_score` violation are returned rather than zeroed.
+pub fn evaluate_ecoimpact_for_node_opts(
+    cfg: &CpvmNodeConfig,
+    cout: f64,
+    options: &EvalOptions,
+) -> Result<EcoImpactResult, CpvmLinkerError> {
+    try_evaluate_ecoimpact_for_node_with_options(cfg, cout, &cfg.meta.cin_unit, options)
+}
+
+/// [`evaluate_ecoimpact_for_node`] with explicit [`EvalOptions`].
+pub fn evaluate_ecoimpact_for_node_with_options(
+    cfg: &CpvmNodeConfig,
+    cout: f64,
+    options: &EvalOptions,
+) -> EcoImpactResult {
+    let cin_unit = &cfg.meta.cin_unit;
+    try_evaluate_ecoimpact_for_node_with_options(cfg, cout, cin_unit, options).unwrap_or(
+        EcoImpactResult {
+            mass_avoided: 0.0,
+            ecoimpactscore: cfg.meta.ecoimpactscore.clamp(0.0, 1.0),
+            karma_gain: 0.0,
+            mass_unit: MassUnit::Unknown,
+            horizon_s: cfg.meta.horizon_s,
+            q_m3_per_s: cfg.meta.q_avg * cfg.meta.q_unit.to_m3_per_s_factor().unwrap_or(0.0),
+        },
+    )
+}
+
+/// Unit-checked variant of [`evaluate_ecoimpact_for_node`].
+///
+/// `cout` is expressed in `cout_unit` and converted into the node's
+/// `cin_unit` before computing mass; discharge is converted to m3/s. Returns
+/// `CpvmLinkerError::Parse` when the concentration units are incompatible
+/// (e.g. MPN/100mL vs ng/L) or the flow unit is unknown.
+pub fn try_evaluate_ecoimpact_for_node(
+    cfg: &CpvmNodeConfig,
+    cout: f64,
+    cout_unit: &ConcentrationUnit,
+) -> Result<EcoImpactResult, CpvmLinkerError> {
+    try_evaluate_ecoimpact_for_node_with_options(cfg, cout, cout_unit, &EvalOptions::default())
+}
+
+/// [`try_evaluate_ecoimpact_for_node`] with explicit [`EvalOptions`].
+pub fn try_evaluate_ecoimpact_for_node_with_options(
+    cfg: &CpvmNodeConfig,
+    cout: f64,
+    cout_unit: &ConcentrationUnit,
+    options: &EvalOptions,
+) -> Result<EcoImpactResult, CpvmLinkerError> {
+    let meta = &cfg.meta;
+
+    if options.strict_score && !(0.0..=1.0).contains(&meta.ecoimpactscore) {
+        return Err(CpvmLinkerError::Parse(format!(
+            "Node {}: ecoimpactscore = {} must be in [0, 1]",
+            meta.node_id.0, meta.ecoimpactscore
+        )));
+    }
+
+    let cout_in_cin_unit =
+        convert_concentration(cout, cout_unit, &meta.cin_unit).ok_or_else(|| {
+            CpvmLinkerError::Parse(format!(
+                "Node {}: cannot convert C_out unit {:?} to C_in unit {:?}",
+                meta.node_id.0, cout_unit, meta.cin_unit
+            ))
+        })?;
+
+    let q_factor = meta.q_unit.to_m3_per_s_factor().ok_or_else(|| {
+        CpvmLinkerError::Parse(format!(
+            "Node {}: unknown flow unit {:?}",
+            meta.node_id.0, meta.q_unit
+        ))
+    })?;
+    let q_m3_per_s = meta.q_avg * q_factor;
+
+    let mass_avoided = try_compute_mass_avoided_with(
+        meta.cin_baseline,
+        cout_in_cin_unit,
+        q_m3_per_s,
+        meta.horizon_s,
+        options.signed_mass,
+    )?;
+
+    let ecoimpactscore = meta.ecoimpactscore.clamp(0.0, 1.0);
+    let karma_gain = ecoimpactscore * mass_avoided * meta.karma_per_unit;
+
+    Ok(EcoImpactResult {
+        mass_avoided,
+        ecoimpactscore,
+        karma_gain,
+        mass_unit: MassUnit::for_load(&meta.cin_unit, &meta.q_unit),
+        horizon_s: meta.horizon_s,
+        q_m3_per_s,
+    })
+}
+
+/// Per-asset transform of a base `ecoimpactscore`.
+pub type ScoreCurve = dyn Fn(&AssetType, f64) -> f64;
+
+/// Apply a per-asset transform to the node's base `ecoimpactscore`, clamping
+/// the result to `[0, 1]`.
+pub fn effective_ecoimpactscore(meta: &CpvmNodeMeta, curve: &ScoreCurve) -> f64 {
+    curve(&meta.asset_type, meta.ecoimpactscore).clamp(0.0, 1.0)
+}
+
+/// Sample weighting curve boosting `RiverReach` scores by 1.2x.
+pub fn river_reach_boost_curve(asset: &AssetType, score: f64) -> f64 {
+    match asset {
+        AssetType::RiverReach => score * 1.2,
+        _ => score,
+    }
+}
+
+/// [`evaluate_ecoimpact_for_node`] with the score passed through
+/// [`effective_ecoimpactscore`]; `None` uses the identity curve.
+pub fn evaluate_ecoimpact_for_node_weighted(
+    cfg: &CpvmNodeConfig,
+    cout: f64,
+    curve: Option<&ScoreCurve>,
+) -> EcoImpactResult {
+    let mut result = evaluate_ecoimpact_for_node(cfg, cout);
+    let identity = |_: &AssetType, score: f64| score;
+    result.ecoimpactscore = effective_ecoimpactscore(&cfg.meta, curve.unwrap_or(&identity));
+    result.karma_gain = result.ecoimpactscore * result.mass_avoided * cfg.meta.karma_per_unit;
+    result
+}
+
+/// Removal physics mapping a proposed C_out to mass avoided over the horizon.
+///
+/// C_out is in the node's `cin_unit`. Karma accounting stays in
+/// [`evaluate_with_model`], so models only describe mass.
+pub trait RemovalModel {
+    fn mass_avoided(&self, meta: &CpvmNodeMeta, cout: f64) -> f64;
+}
+
+/// Default linear model, `max(C_in - C_out, 0) * Q_m3s * horizon_s`, matching
+/// [`evaluate_ecoimpact_for_node`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LinearRemoval;
+
+impl RemovalModel for LinearRemoval {
+    fn mass_avoided(&self, meta: &CpvmNodeMeta, cout: f64) -> f64 {
+        match meta.q_unit.to_m3_per_s_factor() {
+            Some(q_factor) => compute_mass_avoided(
+                meta.cin_baseline,
+                cout,
+                meta.q_avg * q_factor,
+                meta.horizon_s,
+            ),
+            None => 0.0,
+        }
+    }
+}
+
+/// Evaluate a node with mass from `model` and the standard Karma accounting
+/// (`ecoimpactscore * mass_avoided * karma_per_unit`).
+pub fn evaluate_with_model(
+    cfg: &CpvmNodeConfig,
+    cout: f64,
+    model: &dyn RemovalModel,
+) -> EcoImpactResult {
+    let meta = &cfg.meta;
+    let mass_avoided = model.mass_avoided(meta, cout);
+    let ecoimpactscore = meta.ecoimpactscore.clamp(0.0, 1.0);
+    EcoImpactResult {
+        mass_avoided,
+        ecoimpactscore,
+        karma_gain: ecoimpactscore * mass_avoided * meta.karma_per_unit,
+        mass_unit: MassUnit::for_load(&meta.cin_unit, &meta.q_unit),
+        horizon_s: meta.horizon_s,
+        q_m3_per_s: meta.q_avg * meta.q_unit.to_m3_per_s_factor().unwrap_or(0.0),
+    }
+}
+
+/// Evaluate the primary contaminant and, when present, the node's secondary
+/// contaminant.
+///
+/// The secondary result reuses the node's flow, horizon, `ecoimpactscore`,
+/// and `karma_per_unit`, with `cout_secondary` in the secondary `cin_unit`.
+/// It is `None` when the node has no secondary contaminant.
+pub fn evaluate_ecoimpact_secondary(
+    cfg: &CpvmNodeConfig,
+    cout_primary: f64,
+    cout_secondary: f64,
+) -> (EcoImpactResult, Option<EcoImpactResult>) {
+    let primary = evaluate_ecoimpact_for_node(cfg, cout_primary);
+    let secondary = cfg.meta.secondary.as_ref().map(|sec| {
+        let mut sec_cfg = cfg.clone();
+        sec_cfg.meta.cin_baseline = sec.cin_baseline;
+        sec_cfg.meta.cin_unit = sec.cin_unit.clone();
+        evaluate_ecoimpact_for_node(&sec_cfg, cout_secondary)
+    });
+    (primary, secondary)
+}
+
+/// Portion of `horizon_s` that delivers mass downstream.
+///
+/// For a [`AssetType::Reservoir`] with a residence time, water entering in
+/// the last `residence_time_s` seconds has not yet left, so the horizon is
+/// shortened by it (floored at 0). Other nodes use `horizon_s` unchanged.
+pub fn effective_horizon(meta: &CpvmNodeMeta) -> f64 {
+    match (&meta.asset_type, meta.residence_time_s) {
+        (AssetType::Reservoir, Some(residence)) => (meta.horizon_s - residence).max(0.0),
+        _ => meta.horizon_s,
+    }
+}
+
+/// [`evaluate_ecoimpact_for_node`] integrated over [`effective_horizon`].
+pub fn evaluate_ecoimpact_reservoir_aware(cfg: &CpvmNodeConfig, cout: f64) -> EcoImpactResult {
+    let mut delayed = cfg.clone();
+    delayed.meta.horizon_s = effective_horizon(&cfg.meta);
+    evaluate_ecoimpact_for_node(&delayed, cout)
+}
+
+/// Analytic slope dKarma/dC_out in the unsaturated region (`C_out < C_in`).
+///
+/// Equals `-ecoimpactscore * Q * horizon_s * karma_per_unit` with Q in m3/s
+/// and C_out in the node's `cin_unit`; constant in the linear model. Returns
+/// 0.0 for an unknown flow unit, matching the zero result of
+/// [`evaluate_ecoimpact_for_node`].
+pub fn karma_sensitivity(cfg: &CpvmNodeConfig) -> f64 {
+    let meta = &cfg.meta;
+    match meta.q_unit.to_m3_per_s_factor() {
+        Some(q_factor) => {
+            -meta.ecoimpactscore.clamp(0.0, 1.0)
+                * meta.q_avg
+                * q_factor
+                * meta.horizon_s
+                * meta.karma_per_unit
+        }
+        None => 0.0,
+    }
+}
+
+/// dKarma/dC_out at a specific `cout`; 0.0 where `cout >= cin` and the
+/// concentration delta is clamped.
+pub fn karma_sensitivity_at(cfg: &CpvmNodeConfig, cout: f64) -> f64 {
+    if cout >= cfg.meta.cin_baseline {
+        0.0
+    } else {
+        karma_sensitivity(cfg)
+    }
+}
+
+/// Karma ceiling for a node: its Karma at C_out = 0 (full removal).
+///
+/// `ecoimpactscore * cin_baseline * Q_m3s * horizon_s * karma_per_unit`.
+pub fn max_karma(cfg: &CpvmNodeConfig) -> f64 {
+    evaluate_ecoimpact_for_node(cfg, 0.0).karma_gain
+}
+
+/// `(min, max)` Karma attainable with C_out in `[cout_min, cout_max]`.
+///
+/// Karma decreases monotonically in C_out, so the minimum is earned at the
+/// upper bound and the maximum at the lower one. Both bounds are clamped
+/// into `[0, min(cin_baseline, safe_threshold)]`: C_out above the safe
+/// threshold is not a feasible setpoint, and above baseline earns nothing.
+/// Swapped bounds are reordered.
+pub fn karma_envelope(cfg: &CpvmNodeConfig, cout_min: f64, cout_max: f64) -> (f64, f64) {
+    let ceiling = cfg
+        .meta
+        .cin_baseline
+        .min(cfg.safety.safe_threshold)
+        .max(0.0);
+    let (lo, hi) = if cout_min <= cout_max {
+        (cout_min, cout_max)
+    } else {
+        (cout_max, cout_min)
+    };
+    let karma_at =
+        |cout: f64| evaluate_ecoimpact_for_node(cfg, cout.clamp(0.0, ceiling)).karma_gain;
+    (karma_at(hi), karma_at(lo))
+}
+
+/// Evaluate at `cout = cin_baseline * (1 - removal_frac)`, i.e. by removal
+/// efficiency (0.9 = 90% removal). `removal_frac` is clamped into `[0, 1]`;
+/// NaN counts as no removal.
+pub fn evaluate_by_removal_fraction(cfg: &CpvmNodeConfig, removal_frac: f64) -> EcoImpactResult {
+    let frac = if removal_frac.is_nan() {
+        0.0
+    } else {
+        removal_frac.clamp(0.0, 1.0)
+    };
+    evaluate_ecoimpact_for_node(cfg, cfg.meta.cin_baseline * (1.0 - frac))
+}
+
+/// Share of [`max_karma`] captured at `cout`; 0.0 when the ceiling is zero.
+pub fn karma_fraction_achieved(cfg: &CpvmNodeConfig, cout: f64) -> f64 {
+    let max = max_karma(cfg);
+    if max == 0.0 {
+        0.0
+    } else {
+        evaluate_ecoimpact_for_node(cfg, cout).karma_gain / max
+    }
+}
+
+/// Mass avoided per second over `horizon_s`; 0.0 for a non-positive horizon.
+pub fn mass_avoided_rate(result: &EcoImpactResult, horizon_s: f64) -> f64 {
+    if horizon_s > 0.0 {
+        result.mass_avoided / horizon_s
+    } else {
+        0.0
+    }
+}
+
+/// Karma earned per second over `horizon_s`; 0.0 for a non-positive horizon.
+pub fn karma_rate(result: &EcoImpactResult, horizon_s: f64) -> f64 {
+    if horizon_s > 0.0 {
+        result.karma_gain / horizon_s
+    } else {
+        0.0
+    }
+}
+
+/// Rescale mass and karma from `from_horizon` to `to_horizon` seconds,
+/// assuming steady-state operation; `horizon_s` becomes `to_horizon` and
+/// the other fields are unchanged.
+/// A non-positive `from_horizon` yields zero mass and karma.
+pub fn normalize_to_horizon(
+    result: &EcoImpactResult,
+    from_horizon: f64,
+    to_horizon: f64,
+) -> EcoImpactResult {
+    EcoImpactResult {
+        mass_avoided: mass_avoided_rate(result, from_horizon) * to_horizon,
+        karma_gain: karma_rate(result, from_horizon) * to_horizon,
+        horizon_s: to_horizon,
+        ..result.clone()
+    }
+}
+
+/// Precomputed evaluator for a node whose metadata is fixed while C_out moves.
+///
+/// Unit resolution and score clamping happen once in `From`, so
+/// [`karma_for`](Self::karma_for) and [`mass_for`](Self::mass_for) are a
+/// clamp and a multiply. C_out is in the node's `cin_unit`, as in
+/// [`evaluate_ecoimpact_for_node`]; an unknown flow unit yields zero.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NodeEvaluator {
+    cin_baseline: f64,
+    /// `Q_m3s * horizon_s`.
+    mass_factor: f64,
+    /// `ecoimpactscore * Q_m3s * horizon_s * karma_per_unit`.
+    karma_factor: f64,
+}
+
+impl NodeEvaluator {
+    /// Mass avoided for a proposed C_out.
+    pub fn mass_for(&self, cout: f64) -> f64 {
+        (self.cin_baseline - cout).max(0.0) * self.mass_factor
+    }
+
+    /// Karma gain for a proposed C_out.
+    pub fn karma_for(&self, cout: f64) -> f64 {
+        (self.cin_baseline - cout).max(0.0) * self.karma_factor
+    }
+}
+
+impl From<&CpvmNodeConfig> for NodeEvaluator {
+    fn from(cfg: &CpvmNodeConfig) -> Self {
+        let meta = &cfg.meta;
+        let q_m3_per_s = meta.q_avg * meta.q_unit.to_m3_per_s_factor().unwrap_or(0.0);
+        let mass_factor = q_m3_per_s * meta.horizon_s;
+        NodeEvaluator {
+            cin_baseline: meta.cin_baseline,
+            mass_factor,
+            karma_factor: meta.ecoimpactscore.clamp(0.0, 1.0) * mass_factor * meta.karma_per_unit,
+        }
+    }
+}
+
+/// Control-Lyapunov-type viability residual for a proposed C_out.
+///
+/// `V(C_out) = lambda_clf * (max(C_out - C_safe, 0) / C_ref)^2`
+///
+/// This is 0.0 whenever `cout <= safe_threshold` and grows quadratically with
+/// the normalized exceedance above it. A non-positive C_ref cannot normalize
+/// and yields 0.0, matching the CEIM core's treatment of `Cref <= 0`.
+pub fn clf_residual(cfg: &CpvmNodeConfig, cout: f64) -> f64 {
+    let safety = &cfg.safety;
+    if safety.cref <= 0.0 {
+        return 0.0;
+    }
+    let exceedance = (cout - safety.safe_threshold).max(0.0);
+    safety.lambda_clf * (exceedance / safety.cref).powi(2)
+}
+
+/// Control-barrier-type safety residual for a proposed C_out.
+///
+/// `B(C_out) = -mu_cbf * ln(C_safe - C_out)` for `C_out < C_safe`
+///
+/// The log barrier grows without bound as C_out approaches the safe threshold
+/// from below; at or beyond the threshold it returns `f64::INFINITY` to signal
+/// a constraint violation.
+pub fn cbf_residual(cfg: &CpvmNodeConfig, cout: f64) -> f64 {
+    let safety = &cfg.safety;
+    let slack = safety.safe_threshold - cout;
+    if slack.is_nan() || slack <= 0.0 {
+        return f64::INFINITY;
+    }
+    -safety.mu_cbf * slack.ln()
+}
+
+/// Combined safety cost `clf_residual + cbf_residual`.
+///
+/// Finite strictly inside the safe region, infinite at or past the barrier.
+pub fn safety_cost(cfg: &CpvmNodeConfig, cout: f64) -> f64 {
+    clf_residual(cfg, cout) + cbf_residual(cfg, cout)
+}
+
+/// Grid-search C_out over `[cout_min, cout_max]` maximizing
+/// `karma_gain - safety_cost`.
+///
+/// Evaluates `steps + 1` evenly spaced candidates including both endpoints and
+/// returns the best one with its evaluation; ties keep the lower C_out.
+/// Candidates at or beyond the barrier score `-inf` and are never chosen over
+/// a feasible one. If no candidate is feasible, `cout_min` is returned.
+/// Intended as a reference baseline for validating gradient-based controllers.
+pub fn optimize_cout(
+    cfg: &CpvmNodeConfig,
+    cout_min: f64,
+    cout_max: f64,
+    steps: usize,
+) -> (f64, EcoImpactResult) {
+    let mut best_cout = cout_min;
+    let mut best_result = evaluate_ecoimpact_for_node(cfg, cout_min);
+    let mut best_score = best_result.karma_gain - safety_cost(cfg, cout_min);
+
+    for i in 1..=steps {
+        let cout = cout_min + (cout_max - cout_min) * i as f64 / steps as f64;
+        let result = evaluate_ecoimpact_for_node(cfg, cout);
+        let score = result.karma_gain - safety_cost(cfg, cout);
+        if score > best_score || (best_score == f64::NEG_INFINITY && score.is_finite()) {
+            best_cout = cout;
+            best_result = result;
+            best_score = score;
+        }
+    }
+    (best_cout, best_result)
+}
+
+/// Outlet concentration needed to earn `target_karma` over the node's horizon.
+///
+/// Inverts [`evaluate_ecoimpact_for_node`] by solving
+/// `target = score * (C_in - C_out) * Q * t * karma_per_unit` for C_out (in
+/// the node's `cin_unit`). Returns `None` when the target is unreachable
+/// within `[0, C_in]`, or when the score, flow, horizon, or Karma rate is zero
+/// (or the flow unit is unknown) so no C_out can change Karma. A zero target
+/// always yields C_in.
+pub fn cout_for_target_karma(cfg: &CpvmNodeConfig, target_karma: f64) -> Option<f64> {
+    let meta = &cfg.meta;
+    if target_karma == 0.0 {
+        return Some(meta.cin_baseline);
+    }
+    let q_m3_per_s = meta.q_avg * meta.q_unit.to_m3_per_s_factor()?;
+    let karma_per_delta_c =
+        meta.ecoimpactscore.clamp(0.0, 1.0) * q_m3_per_s * meta.horizon_s * meta.karma_per_unit;
+    if karma_per_delta_c == 0.0 || !karma_per_delta_c.is_finite() {
+        return None;
+    }
+    let cout = meta.cin_baseline - target_karma / karma_per_delta_c;
+    (0.0..=meta.cin_baseline).contains(&cout).then_some(cout)
+}
+
+/// Batch evaluation output: per-node results in input order plus their sum.
+#[derive(Debug, Clone)]
+pub struct EcoImpactBatch {
+    pub results: Vec<EcoImpactResult>,
+    /// Sum of `karma_gain` over `results`.
+    pub total_karma: f64,
+}
+
+impl EcoImpactBatch {
+    fn from_results(results: Vec<EcoImpactResult>) -> Self {
+        let total_karma = results.iter().map(|r| r.karma_gain).sum();
+        EcoImpactBatch {
+            results,
+            total_karma,
+        }
+    }
+}
+
+/// Error unless there is exactly one C_out per config.
+fn check_batch_lengths(configs: &[CpvmNodeConfig], couts: &[f64]) -> Result<(), CpvmLinkerError> {
+    if configs.len() != couts.len() {
+        return Err(CpvmLinkerError::Parse(format!(
+            "Batch length mismatch: {} configs vs {} C_out values",
+            configs.len(),
+            couts.len()
+        )));
+    }
+    Ok(())
+}
+
+/// Evaluate a vector of proposed C_out setpoints, one per config.
+///
+/// This is the natural entry point for basin-scale optimizers; results are
+/// returned in input order. Errors if `configs` and `couts` differ in length.
+pub fn evaluate_ecoimpact_batch(
+    configs: &[CpvmNodeConfig],
+    couts: &[f64],
+) -> Result<EcoImpactBatch, CpvmLinkerError> {
+    check_batch_lengths(configs, couts)?;
+    let results = configs
+        .iter()
+        .zip(couts)
+        .map(|(cfg, &cout)| evaluate_ecoimpact_for_node(cfg, cout))
+        .collect();
+    Ok(EcoImpactBatch::from_results(results))
+}
+
+/// Parallel counterpart of [`evaluate_ecoimpact_batch`] using rayon.
+///
+/// Each node is evaluated independently and collected in input order; the
+/// total is summed serially afterwards, so output is bit-identical to the
+/// serial batch regardless of thread scheduling.
+#[cfg(feature = "parallel")]
+pub fn evaluate_ecoimpact_batch_par(
+    configs: &[CpvmNodeConfig],
+    couts: &[f64],
+) -> Result<EcoImpactBatch, CpvmLinkerError> {
+    use rayon::prelude::*;
+
+    check_batch_lengths(configs, couts)?;
+    let results = configs
+        .par_iter()
+        .zip(couts.par_iter())
+        .map(|(cfg, &cout)| evaluate_ecoimpact_for_node(cfg, cout))
+        .collect();
+    Ok(EcoImpactBatch::from_results(results))
+}
+
+/// Example helper: build configs for all nodes from a qpudatashard path.
+///
+/// Callers can then wire these configs into local controllers, smart-city
+/// orchestrators, or basin-scale optimizers.
+pub fn build_cpvm_configs_from_shard(
+    path: &str,
+    cref_default: f64,
+    lambda_clf: f64,
+    mu_cbf: f64,
+) -> Result<Vec<CpvmNodeConfig>, CpvmLinkerError> {
+    let metas = load_cpvm_nodes_from_csv(path)?;
+    let configs = metas
+        .into_iter()
+        .map(|m| bind_cpvm_config(m, cref_default, lambda_clf, mu_cbf))
+        .collect();
+    Ok(configs)
+}
+
+/// Load a shard and evaluate every node against a `NodeId -> C_out` map.
+///
+/// Unmapped nodes are evaluated at their `cin_baseline` (zero mass). Results
+/// are returned in load order.
+pub fn evaluate_shard(
+    path: &str,
+    couts: &HashMap<NodeId, f64>,
+    defaults: SafetyDefaults,
+) -> Result<Vec<(NodeId, EcoImpactResult)>, CpvmLinkerError> {
+    Ok(evaluate_metas(
+        load_cpvm_nodes_from_csv(path)?,
+        couts,
+        defaults,
+    ))
+}
+
+/// Bind and evaluate loaded nodes; unmapped nodes stay at baseline.
+fn evaluate_metas(
+    metas: Vec<CpvmNodeMeta>,
+    couts: &HashMap<NodeId, f64>,
+    defaults: SafetyDefaults,
+) -> Vec<(NodeId, EcoImpactResult)> {
+    metas
+        .into_iter()
+        .map(|m| {
+            let cfg = bind_cpvm_config(m, defaults.cref, defaults.lambda_clf, defaults.mu_cbf);
+            let cout = couts
+                .get(&cfg.meta.node_id)
+                .copied()
+                .unwrap_or(cfg.meta.cin_baseline);
+            let result = evaluate_ecoimpact_for_node(&cfg, cout);
+            (cfg.meta.node_id, result)
+        })
+        .collect()
+}
+
+/// Load a companion `node_id,cout,cout_unit` setpoints CSV.
+///
+/// A leading `node_id` header row, blank lines, and `#` comments are
+/// skipped, as are rows with a blank `node_id` or a missing or blank `cout`
+/// (those nodes stay at baseline in [`evaluate_shard_with_setpoints`]). A
+/// blank or missing `cout_unit` is reported as mg/L. A later row for the same
+/// NodeId replaces an earlier one.
+pub fn load_cout_setpoints(
+    path: &str,
+) -> Result<HashMap<NodeId, (f64, ConcentrationUnit)>, CpvmLinkerError> {
+    Ok(read_cout_setpoints(path)?
+        .into_iter()
+        .map(|(id, (cout, unit))| (id, (cout, unit.unwrap_or(ConcentrationUnit::MgPerL))))
+        .collect())
+}
+
+/// [`load_cout_setpoints`] keeping a blank `cout_unit` as `None`, so
+/// [`evaluate_shard_with_setpoints`] can read it in the node's `cin_unit`.
+fn read_cout_setpoints(
+    path: &str,
+) -> Result<HashMap<NodeId, (f64, Option<ConcentrationUnit>)>, CpvmLinkerError> {
+    let mut reader = CpvmNodeReader::with_header(open_shard(path)?, None, LoaderOptions::default());
+    let mut setpoints = HashMap::new();
+    let mut first = true;
+    while let Some(line) = reader.next_content_line() {
+        let fields = split_csv_line(&line?);
+        let line_no = reader.line_no;
+        if std::mem::take(&mut first) && fields[0].eq_ignore_ascii_case("node_id") {
+            continue;
+        }
+        if fields[0].is_empty() || fields.get(1).is_none_or(String::is_empty) {
+            continue;
+        }
+        let cout =
+            parse_f64_loose(&fields[1]).map_err(|e| field_parse_error(line_no, "cout", e))?;
+        let unit = match fields.get(2).map(String::as_str) {
+            None | Some("") => None,
+            Some(raw) => Some(ConcentrationUnit::from_str(raw)),
+        };
+        setpoints.insert(NodeId(fields[0].clone()), (cout, unit));
+    }
+    Ok(setpoints)
+}
+
+/// [`evaluate_shard`] with C_out taken from a [`load_cout_setpoints`] file.
+///
+/// Each setpoint is converted into its node's `cin_unit`; a setpoint with a
+/// blank `cout_unit` is taken to be in `cin_unit` already, and a unit that
+/// cannot be converted is a `Parse` error naming the node. Nodes without a setpoint are evaluated
+/// at baseline.
+pub fn evaluate_shard_with_setpoints(
+    path: &str,
+    setpoints_path: &str,
+    defaults: SafetyDefaults,
+) -> Result<Vec<(NodeId, EcoImpactResult)>, CpvmLinkerError> {
+    let setpoints = read_cout_setpoints(setpoints_path)?;
+    let metas = load_cpvm_nodes_from_csv(path)?;
+    let mut couts = HashMap::new();
+    for meta in &metas {
+        if let Some((cout, unit)) = setpoints.get(&meta.node_id) {
+            let unit = unit.as_ref().unwrap_or(&meta.cin_unit);
+            let converted =
+                convert_concentration(*cout, unit, &meta.cin_unit).ok_or_else(|| {
+                    CpvmLinkerError::Parse(format!(
+                        "Node {}: cannot convert C_out unit {:?} to C_in unit {:?}",
+                        meta.node_id.0, unit, meta.cin_unit
+                    ))
+                })?;
+            couts.insert(meta.node_id.clone(), converted);
+        }
+    }
+    Ok(evaluate_metas(metas, &couts, defaults))
+}
+
+/// Per-region rollup of evaluation results.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RegionSummary {
+    pub total_mass_avoided: f64,
+    pub total_karma_gain: f64,
+    pub node_count: usize,
+    /// Mean eco-impact score over the region's nodes.
+    pub mean_ecoimpactscore: f64,
+}
+
+impl RegionSummary {
+    /// Fold one node's result into the summary.
+    fn add(&mut self, result: &EcoImpactResult) {
+        self.total_mass_avoided += result.mass_avoided;
+        self.total_karma_gain += result.karma_gain;
+        self.node_count += 1;
+        self.mean_ecoimpactscore +=
+            (result.ecoimpactscore - self.mean_ecoimpactscore) / self.node_count as f64;
+    }
+}
+
+/// Streaming counterpart to [`aggregate_by_region`].
+///
+/// Fold results in one at a time, e.g. while iterating a [`CpvmNodeReader`],
+/// without collecting them.
+#[derive(Debug, Clone, Default)]
+pub struct RunningAggregate {
+    total: RegionSummary,
+    by_region: HashMap<Region, RegionSummary>,
+}
+
+impl RunningAggregate {
+    pub fn new() -> Self {
+        RunningAggregate::default()
+    }
+
+    /// Fold one node's result into the overall and per-region sums.
+    pub fn add(&mut self, region: &Region, result: &EcoImpactResult) {
+        self.total.add(result);
+        self.by_region
+            .entry(region.clone())
+            .or_default()
+            .add(result);
+    }
+
+    /// Running totals across every region.
+    pub fn total(&self) -> &RegionSummary {
+        &self.total
+    }
+
+    /// Running sums for one region, if it has been seen.
+    pub fn region(&self, region: &Region) -> Option<&RegionSummary> {
+        self.by_region.get(region)
+    }
+
+    /// Running sums for every region seen so far.
+    pub fn by_region(&self) -> &HashMap<Region, RegionSummary> {
+        &self.by_region
+    }
+
+    /// Consume the aggregate, keeping only the per-region sums.
+    pub fn into_by_region(self) -> HashMap<Region, RegionSummary> {
+        self.by_region
+    }
+}
+
+/// Group evaluation results by node `region` for leaderboard-style reports.
+pub fn aggregate_by_region(
+    results: &[(CpvmNodeMeta, EcoImpactResult)],
+) -> HashMap<Region, RegionSummary> {
+    let mut running = RunningAggregate::new();
+    for (meta, result) in results {
+        running.add(&meta.region, result);
+    }
+    running.into_by_region()
+}
+
+/// Total `mass_avoided` per region expressed in `target`.
+///
+/// Each result is converted from its own `mass_unit`, so nodes measured in
+/// ng/L and mg/L sum meaningfully. Errors naming the node if either unit is
+/// [`MassUnit::Unknown`].
+pub fn aggregate_mass_in_unit(
+    results: &[(CpvmNodeMeta, EcoImpactResult)],
+    target: MassUnit,
+) -> Result<HashMap<Region, f64>, CpvmLinkerError> {
+    let target_factor = target.to_kg_factor().ok_or_else(|| {
+        CpvmLinkerError::Parse("Cannot aggregate mass into an unknown unit".to_string())
+    })?;
+    let mut totals: HashMap<Region, f64> = HashMap::new();
+    for (meta, result) in results {
+        let factor = result.mass_unit.to_kg_factor().ok_or_else(|| {
+            CpvmLinkerError::Parse(format!(
+                "Node {}: mass unit {} cannot be converted to {}",
+                meta.node_id,
+                result.mass_unit.as_canonical_str(),
+                target.as_canonical_str()
+            ))
+        })?;
+        *totals.entry(meta.region.clone()).or_insert(0.0) +=
+            result.mass_avoided * factor / target_factor;
+    }
+    Ok(totals)
+}
+
+/// [`aggregate_by_region`] as a vector sorted by region name, for stable
+/// report output and snapshot tests.
+pub fn aggregate_by_region_sorted(
+    results: &[(CpvmNodeMeta, EcoImpactResult)],
+) -> Vec<(Region, RegionSummary)> {
+    let mut regions: Vec<(Region, RegionSummary)> =
+        aggregate_by_region(results).into_iter().collect();
+    regions.sort_by(|a, b| a.0 .0.cmp(&b.0 .0));
+    regions
+}
+
+/// [`aggregate_by_region`] sorted by descending total Karma; ties fall back
+/// to region name.
+pub fn aggregate_by_region_sorted_by_karma(
+    results: &[(CpvmNodeMeta, EcoImpactResult)],
+) -> Vec<(Region, RegionSummary)> {
+    let mut regions = aggregate_by_region_sorted(results);
+    regions.sort_by(|a, b| b.1.total_karma_gain.total_cmp(&a.1.total_karma_gain));
+    regions
+}
+
+/// Summed totals for one `(waterbody, asset type)` cross-tab cell.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GroupSummary {
+    pub total_mass_avoided: f64,
+    pub total_karma_gain: f64,
+    pub node_count: usize,
+}
+
+/// Cross-tabulate evaluation results by waterbody and asset type.
+pub fn aggregate_by_waterbody_asset(
+    results: &[(CpvmNodeMeta, EcoImpactResult)],
+) -> HashMap<(Waterbody, AssetType), GroupSummary> {
+    let mut cells: HashMap<(Waterbody, AssetType), GroupSummary> = HashMap::new();
+    for (meta, result) in results {
+        let cell = cells
+            .entry((meta.waterbody.clone(), meta.asset_type.clone()))
+            .or_default();
+        cell.total_mass_avoided += result.mass_avoided;
+        cell.total_karma_gain += result.karma_gain;
+        cell.node_count += 1;
+    }
+    cells
+}
+
+/// One ledger entry: a Karma credit (or, if negative, a debit) for a node.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KarmaTxn {
+    pub node: NodeId,
+    pub gain: f64,
+    pub timestamp_s: f64,
+}
+
+/// Append-only Karma ledger with per-node running balances.
+///
+/// Every credit is recorded as a [`KarmaTxn`], so balances can always be
+/// audited against the transaction log.
+#[derive(Debug, Clone, Default)]
+pub struct KarmaLedger {
+    txns: Vec<KarmaTxn>,
+    balances: HashMap<NodeId, f64>,
+}
+
+impl KarmaLedger {
+    pub fn new() -> Self {
+        KarmaLedger::default()
+    }
+
+    /// Record a Karma gain for `node`. Negative gains (governance clawbacks)
+    /// are allowed and reduce the node's balance.
+    pub fn credit(&mut self, node: &NodeId, gain: f64, timestamp_s: f64) {
+        *self.balances.entry(node.clone()).or_insert(0.0) += gain;
+        self.txns.push(KarmaTxn {
+            node: node.clone(),
+            gain,
+            timestamp_s,
+        });
+    }
+
+    /// Running balance for `node`, 0.0 if it has no transactions.
+    pub fn balance(&self, node: &NodeId) -> f64 {
+        self.balances.get(node).copied().unwrap_or(0.0)
+    }
+
+    /// Sum of all node balances.
+    pub fn total_balance(&self) -> f64 {
+        self.balances.values().sum()
+    }
+
+    /// Eagerly rematerialize balances with exponential decay as of `now_s`.
+    ///
+    /// Each transaction contributes `gain * 0.5^((now_s - timestamp_s) / half_life_s)`,
+    /// recomputed from the full log, so repeated calls do not compound. The
+    /// transaction log keeps undecayed gains for audit; credits made after
+    /// this call count at full value until the next decay. Transactions
+    /// stamped after `now_s` are not grown. A non-positive or non-finite
+    /// `half_life_s` leaves balances untouched.
+    pub fn apply_decay(&mut self, now_s: f64, half_life_s: f64) {
+        if !half_life_s.is_finite() || half_life_s <= 0.0 {
+            return;
+        }
+        self.balances.clear();
+        for txn in &self.txns {
+            let age = (now_s - txn.timestamp_s).max(0.0);
+            let decayed = txn.gain * 0.5_f64.powf(age / half_life_s);
+            *self.balances.entry(txn.node.clone()).or_insert(0.0) += decayed;
+        }
+    }
+
+    /// All transactions in the order they were credited.
+    pub fn transactions(&self) -> &[KarmaTxn] {
+        &self.txns
+    }
+}
+
+/// Node count per asset type, most common first; ties are ordered by
+/// canonical name. Each distinct `AssetType::Other` string is its own entry.
+pub fn asset_type_census(nodes: &[CpvmNodeMeta]) -> Vec<(AssetType, usize)> {
+    let mut counts: HashMap<&AssetType, usize> = HashMap::new();
+    for node in nodes {
+        *counts.entry(&node.asset_type).or_insert(0) += 1;
+    }
+    let mut census: Vec<(AssetType, usize)> = counts
+        .into_iter()
+        .map(|(asset, n)| (asset.clone(), n))
+        .collect();
+    census.sort_by(|(a, na), (b, nb)| {
+        nb.cmp(na)
+            .then_with(|| a.as_canonical_str().cmp(b.as_canonical_str()))
+    });
+    census
+}
+
+/// Select nodes matching every provided criterion; `None` matches anything.
+///
+/// `AssetType::Other` matches on its inner string.
+pub fn filter_nodes<'a>(
+    nodes: &'a [CpvmNodeMeta],
+    asset: Option<&AssetType>,
+    region: Option<&Region>,
+) -> Vec<&'a CpvmNodeMeta> {
+    nodes
+        .iter()
+        .filter(|n| asset.is_none_or(|a| &n.asset_type == a))
+        .filter(|n| region.is_none_or(|r| &n.region == r))
+        .collect()
+}
+
+/// Select nodes carrying `tag` (exact, case-sensitive match).
+pub fn filter_by_tag<'a>(nodes: &'a [CpvmNodeMeta], tag: &str) -> Vec<&'a CpvmNodeMeta> {
+    nodes
+        .iter()
+        .filter(|n| n.tags.iter().any(|t| t == tag))
+        .collect()
+}
+
+/// Parse error naming a repeated `NodeId`.
+fn duplicate_node_error(node: &NodeId) -> CpvmLinkerError {
+    CpvmLinkerError::Parse(format!("Duplicate node_id: {}", node.0))
+}
+
+/// Index owned configs by `NodeId`. Later duplicates replace earlier ones;
+/// use [`try_index_configs_by_id`] to reject them instead.
+pub fn index_configs_by_id(configs: Vec<CpvmNodeConfig>) -> HashMap<NodeId, CpvmNodeConfig> {
+    configs
+        .into_iter()
+        .map(|cfg| (cfg.meta.node_id.clone(), cfg))
+        .collect()
+}
+
+/// Index owned configs by `NodeId`, erroring on the first duplicate.
+pub fn try_index_configs_by_id(
+    configs: Vec<CpvmNodeConfig>,
+) -> Result<HashMap<NodeId, CpvmNodeConfig>, CpvmLinkerError> {
+    let mut index = HashMap::with_capacity(configs.len());
+    for cfg in configs {
+        if index.contains_key(&cfg.meta.node_id) {
+            return Err(duplicate_node_error(&cfg.meta.node_id));
+        }
+        index.insert(cfg.meta.node_id.clone(), cfg);
+    }
+    Ok(index)
+}
+
+/// Borrowing variant of [`index_configs_by_id`].
+pub fn index_configs_by_id_ref(configs: &[CpvmNodeConfig]) -> HashMap<&NodeId, &CpvmNodeConfig> {
+    configs.iter().map(|cfg| (&cfg.meta.node_id, cfg)).collect()
+}
+
+/// Borrowing variant of [`try_index_configs_by_id`].
+pub fn try_index_configs_by_id_ref(
+    configs: &[CpvmNodeConfig],
+) -> Result<HashMap<&NodeId, &CpvmNodeConfig>, CpvmLinkerError> {
+    let mut index = HashMap::with_capacity(configs.len());
+    for cfg in configs {
+        if index.insert(&cfg.meta.node_id, cfg).is_some() {
+            return Err(duplicate_node_error(&cfg.meta.node_id));
+        }
+    }
+    Ok(index)
+}
+
+/// Distribution statistics over a run's Karma gains.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ResultStats {
+    pub count: usize,
+    pub total_karma: f64,
+    pub mean_karma: f64,
+    pub median_karma: f64,
+    pub min_karma: f64,
+    pub max_karma: f64,
+    /// Population standard deviation.
+    pub std_dev_karma: f64,
+}
+
+/// Summarize Karma gains; an empty slice yields all zeros with `count = 0`.
+pub fn summarize_results(results: &[EcoImpactResult]) -> ResultStats {
+    if results.is_empty() {
+        return ResultStats::default();
+    }
+
+    let mut karma: Vec<f64> = results.iter().map(|r| r.karma_gain).collect();
+    karma.sort_by(f64::total_cmp);
+
+    let count = karma.len();
+    let total_karma: f64 = karma.iter().sum();
+    let mean_karma = total_karma / count as f64;
+    let median_karma = if count.is_multiple_of(2) {
+        0.5 * (karma[count / 2 - 1] + karma[count / 2])
+    } else {
+        karma[count / 2]
+    };
+    let variance = karma.iter().map(|k| (k - mean_karma).powi(2)).sum::<f64>() / count as f64;
+
+    ResultStats {
+        count,
+        total_karma,
+        mean_karma,
+        median_karma,
+        min_karma: karma[0],
+        max_karma: karma[count - 1],
+        std_dev_karma: variance.sqrt(),
+    }
+}
+
+/// Priority-weighted portfolio Karma.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WeightedKarma {
+    /// Sum of `karma_gain * weight`, with weight 1.0 for unweighted nodes.
+    pub total: f64,
+    /// Fraction of nodes that had an explicit weight (0.0 for no nodes).
+    pub weight_coverage: f64,
+}
+
+/// Sum Karma using governance priority weights keyed by `NodeId`.
+///
+/// Nodes without an entry in `weights` count at weight 1.0; `weight_coverage`
+/// lets reports flag how much of the portfolio was actually weighted.
+pub fn weighted_total_karma(
+    results: &[(NodeId, EcoImpactResult)],
+    weights: &HashMap<NodeId, f64>,
+) -> WeightedKarma {
+    let mut total = 0.0;
+    let mut weighted = 0usize;
+    for (node, result) in results {
+        let weight = match weights.get(node) {
+            Some(&w) => {
+                weighted += 1;
+                w
+            }
+            None => 1.0,
+        };
+        total += result.karma_gain * weight;
+    }
+    let weight_coverage = if results.is_empty() {
+        0.0
+    } else {
+        weighted as f64 / results.len() as f64
+    };
+    WeightedKarma {
+        total,
+        weight_coverage,
+    }
+}
+
+/// Write a pretty-printed (2-space indent) JSON summary of a control run.
+///
+/// The document holds `generated_nodes`, `total_karma`, `total_mass_avoided`,
+/// a `by_region` map from [`aggregate_by_region`], and a `nodes` array with
+/// one entry per result.
+#[cfg(feature = "serde")]
+pub fn export_run_report<W: Write>(
+    writer: W,
+    results: &[(CpvmNodeMeta, EcoImpactResult)],
+) -> Result<(), CpvmLinkerError> {
+    use serde_json::{json, Map, Value};
+
+    let by_region: Map<String, Value> = aggregate_by_region(results)
+        .into_iter()
+        .map(|(region, summary)| {
+            let entry = json!({
+                "total_mass_avoided": summary.total_mass_avoided,
+                "total_karma_gain": summary.total_karma_gain,
+                "node_count": summary.node_count,
+                "mean_ecoimpactscore": summary.mean_ecoimpactscore,
+            });
+            (region.0, entry)
+        })
+        .collect();
+
+    let nodes: Vec<Value> = results
+        .iter()
+        .map(|(meta, result)| {
+            json!({
+                "node_id": meta.node_id.0,
+                "asset_type": meta.asset_type.as_canonical_str(),
+                "waterbody": meta.waterbody.0,
+                "region": meta.region.0,
+                "tags": meta.tags,
+                "mass_avoided": result.mass_avoided,
+                "mass_unit": result.mass_unit.as_canonical_str(),
+                "ecoimpactscore": result.ecoimpactscore,
+                "karma_gain": result.karma_gain,
+            })
+        })
+        .collect();
+
+    let report = json!({
+        "generated_nodes": results.len(),
+        "total_karma": results.iter().map(|(_, r)| r.karma_gain).sum::<f64>(),
+        "total_mass_avoided": results.iter().map(|(_, r)| r.mass_avoided).sum::<f64>(),
+        "by_region": by_region,
+        "nodes": nodes,
+    });
+
+    serde_json::to_writer_pretty(writer, &report).map_err(|e| CpvmLinkerError::Io(e.into()))
+}
+
+/// Write results as a GeoJSON `FeatureCollection` of `Point` features with
+/// `node_id`, `region`, and `karma_gain` properties.
+///
+/// Nodes without both `lat` and `lon` are skipped.
+#[cfg(feature = "serde")]
+pub fn export_geojson<W: Write>(
+    writer: W,
+    results: &[(CpvmNodeMeta, EcoImpactResult)],
+) -> Result<(), CpvmLinkerError> {
+    use serde_json::{json, Value};
+
+    let features: Vec<Value> = results
+        .iter()
+        .filter_map(|(meta, result)| {
+            let (lat, lon) = (meta.lat?, meta.lon?);
+            Some(json!({
+                "type": "Feature",
+                "geometry": { "type": "Point", "coordinates": [lon, lat] },
+                "properties": {
+                    "node_id": meta.node_id.0,
+                    "region": meta.region.0,
+                    "karma_gain": result.karma_gain,
+                },
+            }))
+        })
+        .collect();
+
+    let collection = json!({ "type": "FeatureCollection", "features": features });
+    serde_json::to_writer(writer, &collection).map_err(|e| CpvmLinkerError::Io(e.into()))
+}
+
+/// Bucket node `ecoimpactscore`s into `bins` equal-width bins over `[0, 1]`.
+///
+/// Returns `(bin_lo, bin_hi, count)` per bin. Scores are clamped into range
+/// first, so a score of exactly 1.0 lands in the last bin; NaN scores are
+/// skipped. Returns `CpvmLinkerError::Parse` if `bins == 0`.
+pub fn try_ecoimpactscore_histogram(
+    nodes: &[CpvmNodeMeta],
+    bins: usize,
+) -> Result<Vec<(f64, f64, usize)>, CpvmLinkerError> {
+    if bins == 0 {
+        return Err(CpvmLinkerError::Parse(
+            "ecoimpactscore histogram needs at least one bin".to_string(),
+        ));
+    }
+    let width = 1.0 / bins as f64;
+    let mut counts = vec![0usize; bins];
+    for meta in nodes.iter().filter(|m| !m.ecoimpactscore.is_nan()) {
+        let score = meta.ecoimpactscore.clamp(0.0, 1.0);
+        let idx = ((score * bins as f64) as usize).min(bins - 1);
+        counts[idx] += 1;
+    }
+    Ok(counts
+        .into_iter()
+        .enumerate()
+        .map(|(i, count)| (i as f64 * width, (i + 1) as f64 * width, count))
+        .collect())
+}
+
+/// Infallible variant of [`try_ecoimpactscore_histogram`]; `bins == 0`
+/// yields an empty vector.
+pub fn ecoimpactscore_histogram(nodes: &[CpvmNodeMeta], bins: usize) -> Vec<(f64, f64, usize)> {
+    try_ecoimpactscore_histogram(nodes, bins).unwrap_or_default()
+}
+
+/// Margin of the baseline over the safe threshold; negative when compliant.
+pub fn violation_margin(cfg: &CpvmNodeConfig) -> f64 {
+    cfg.meta.cin_baseline - cfg.safety.safe_threshold
+}
+
+/// Deterministic content hash of a config set for change detection between
+/// deployments.
+///
+/// Every meta and safety field is serialized in a fixed order, with floats
+/// rounded to 9 significant digits, and the per-node records are sorted, so
+/// input order does not matter. The hash is 64-bit FNV-1a, which (unlike
+/// `std`'s `DefaultHasher`) is stable across Rust releases.
+pub fn config_set_fingerprint(configs: &[CpvmNodeConfig]) -> u64 {
+    fn num(v: f64) -> String {
+        format!("{:.8e}", v)
+    }
+    fn opt(v: Option<f64>) -> String {
+        v.map(num).unwrap_or_default()
+    }
+
+    let mut records: Vec<String> = configs
+        .iter()
+        .map(|cfg| {
+            let (m, s) = (&cfg.meta, &cfg.safety);
+            let (sec_name, sec_cin, sec_unit) = match &m.secondary {
+                Some(sec) => (
+                    sec.name.as_str(),
+                    num(sec.cin_baseline),
+                    sec.cin_unit.to_string(),
+                ),
+                None => ("", String::new(), String::new()),
+            };
+            let fields = [
+                m.node_id.0.clone(),
+                m.asset_type.as_canonical_str().to_string(),
+                m.waterbody.0.clone(),
+                m.region.0.clone(),
+                m.cpvm_profile.clone(),
+                num(m.cin_baseline),
+                m.cin_unit.to_string(),
+                num(m.q_avg),
+                m.q_unit.to_string(),
+                num(m.horizon_s),
+                num(m.ecoimpactscore),
+                num(m.karma_per_unit),
+                m.notes.clone(),
+                m.tags.join("|"),
+                sec_name.to_string(),
+                sec_cin,
+                sec_unit,
+                opt(m.residence_time_s),
+                opt(m.lat),
+                opt(m.lon),
+                num(s.safe_threshold),
+                num(s.cref),
+                num(s.lambda_clf),
+                num(s.mu_cbf),
+            ];
+            fields.join("\u{1f}")
+        })
+        .collect();
+    records.sort();
+
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for record in &records {
+        for byte in record.bytes().chain(std::iter::once(b'\n')) {
+            hash ^= u64::from(byte);
+            hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+    hash
+}
+
+/// Configs whose baseline already exceeds the safe threshold, i.e. nodes out
+/// of compliance before any control action.
+pub fn find_threshold_violations(configs: &[CpvmNodeConfig]) -> Vec<&CpvmNodeConfig> {
+    configs
+        .iter()
+        .filter(|cfg| violation_margin(cfg) > 0.0)
+        .collect()
+}
+
+/// Set `safety.safe_threshold` from externally recomputed `overrides`.
+///
+/// Every config whose NodeId has an entry is updated (duplicated NodeIds
+/// all receive it); others are left unchanged. Returns the number of configs
+/// updated and the override NodeIds that matched no config, sorted by id.
+pub fn apply_threshold_overrides(
+    configs: &mut [CpvmNodeConfig],
+    overrides: &HashMap<NodeId, f64>,
+) -> (usize, Vec<NodeId>) {
+    let mut applied = 0;
+    let mut matched = HashSet::new();
+    for cfg in configs.iter_mut() {
+        if let Some(&threshold) = overrides.get(&cfg.meta.node_id) {
+            cfg.safety.safe_threshold = threshold;
+            matched.insert(cfg.meta.node_id.clone());
+            applied += 1;
+        }
+    }
+    let mut misses: Vec<NodeId> = overrides
+        .keys()
+        .filter(|id| !matched.contains(*id))
+        .cloned()
+        .collect();
+    misses.sort_by(|a, b| a.0.cmp(&b.0));
+    (applied, misses)
+}
+
+/// NodeIds whose proposed `cout` meets their safe threshold
+/// (`cout <= safe_threshold`), in input order.
+pub fn compliant_nodes(
+    configs: &[CpvmNodeConfig],
+    couts: &[f64],
+) -> Result<Vec<NodeId>, CpvmLinkerError> {
+    check_batch_lengths(configs, couts)?;
+    Ok(configs
+        .iter()
+        .zip(couts)
+        .filter(|(cfg, &cout)| cout <= cfg.safety.safe_threshold)
+        .map(|(cfg, _)| cfg.meta.node_id.clone())
+        .collect())
+}
+
+/// Fraction of nodes in [`compliant_nodes`]; 0.0 for an empty set.
+pub fn compliance_rate(configs: &[CpvmNodeConfig], couts: &[f64]) -> Result<f64, CpvmLinkerError> {
+    let compliant = compliant_nodes(configs, couts)?.len();
+    if configs.is_empty() {
+        Ok(0.0)
+    } else {
+        Ok(compliant as f64 / configs.len() as f64)
+    }
+}
+
+/// Nodes whose `mass_avoided` exceeds the physical maximum
+/// `cin_baseline * Q_m3s * horizon_s` (full removal, C_out = 0).
+///
+/// A small relative tolerance absorbs rounding; an unknown flow unit has a
+/// maximum of zero. Flags configuration and unit-conversion bugs.
+pub fn check_mass_feasibility(results: &[(CpvmNodeMeta, EcoImpactResult)]) -> Vec<NodeId> {
+    results
+        .iter()
+        .filter(|(meta, result)| {
+            let q_m3_per_s = meta.q_avg * meta.q_unit.to_m3_per_s_factor().unwrap_or(0.0);
+            let max_mass = meta.cin_baseline * q_m3_per_s * meta.horizon_s;
+            result.mass_avoided > max_mass + 1e-9 * max_mass.abs()
+        })
+        .map(|(meta, _)| meta.node_id.clone())
+        .collect()
+}
+
+/// Waterbodies whose nodes use more than one concentration unit.
+///
+/// Mixed `ng/L` and `mg/L` rows silently break aggregation; run this right
+/// after load. Waterbodies and units are listed in order of first appearance.
+pub fn check_unit_consistency(nodes: &[CpvmNodeMeta]) -> Vec<(Waterbody, Vec<ConcentrationUnit>)> {
+    let mut groups: Vec<(Waterbody, Vec<ConcentrationUnit>)> = Vec::new();
+    let mut positions: HashMap<&Waterbody, usize> = HashMap::new();
+    for node in nodes {
+        let idx = *positions.entry(&node.waterbody).or_insert_with(|| {
+            groups.push((node.waterbody.clone(), Vec::new()));
+            groups.len() - 1
+        });
+        let units = &mut groups[idx].1;
+        if !units.contains(&node.cin_unit) {
+            units.push(node.cin_unit.clone());
+        }
+    }
+    groups.retain(|(_, units)| units.len() > 1);
+    groups
+}
+
+/// Per-node change between two evaluation runs; see [`diff_runs`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunDiff {
+    pub node: NodeId,
+    pub karma_before: f64,
+    pub karma_after: f64,
+    pub karma_delta: f64,
+    pub mass_delta: f64,
+}
+
+/// Match two runs by `NodeId` and report Karma and mass deltas.
+///
+/// Nodes present in only one run count as 0 on the missing side. Output is
+/// sorted by descending absolute Karma delta; ties keep first-seen order.
+pub fn diff_runs(
+    before: &[(NodeId, EcoImpactResult)],
+    after: &[(NodeId, EcoImpactResult)],
+) -> Vec<RunDiff> {
+    let after_by_id: HashMap<&NodeId, &EcoImpactResult> =
+        after.iter().map(|(id, r)| (id, r)).collect();
+    let before_ids: HashSet<&NodeId> = before.iter().map(|(id, _)| id).collect();
+
+    let diff = |node: &NodeId, b: Option<&EcoImpactResult>, a: Option<&EcoImpactResult>| {
+        let karma_before = b.map_or(0.0, |r| r.karma_gain);
+        let karma_after = a.map_or(0.0, |r| r.karma_gain);
+        RunDiff {
+            node: node.clone(),
+            karma_before,
+            karma_after,
+            karma_delta: karma_after - karma_before,
+            mass_delta: a.map_or(0.0, |r| r.mass_avoided) - b.map_or(0.0, |r| r.mass_avoided),
+        }
+    };
+
+    let mut diffs: Vec<RunDiff> = before
+        .iter()
+        .map(|(id, b)| diff(id, Some(b), after_by_id.get(id).copied()))
+        .collect();
+    diffs.extend(
+        after
+            .iter()
+            .filter(|(id, _)| !before_ids.contains(id))
+            .map(|(id, a)| diff(id, None, Some(a))),
+    );
+    diffs.sort_by(|x, y| y.karma_delta.abs().total_cmp(&x.karma_delta.abs()));
+    diffs
+}
+
+/// Flow-weighted basin outlet concentration `sum(C_out * Q) / sum(Q)`.
+///
+/// Takes `(meta, cout)` pairs with C_out in each node's `cin_unit`; flows are
+/// converted to m3/s first. Returns `None` for empty input, zero total flow,
+/// an unknown flow unit, or mixed concentration units.
+pub fn basin_blended_cout(results: &[(CpvmNodeMeta, f64)]) -> Option<f64> {
+    let unit = &results.first()?.0.cin_unit;
+    let mut load = 0.0;
+    let mut flow = 0.0;
+    for (meta, cout) in results {
+        if &meta.cin_unit != unit {
+            return None;
+        }
+        let q = meta.q_avg * meta.q_unit.to_m3_per_s_factor()?;
+        load += cout * q;
+        flow += q;
+    }
+    if flow == 0.0 {
+        None
+    } else {
+        Some(load / flow)
+    }
+}
+
+/// Escape a Prometheus label value: backslash, double quote, and newline.
+fn escape_prometheus_label(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            '\n' => escaped.push_str("\\n"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Format a sample value in Prometheus text syntax (`NaN`, `+Inf`, `-Inf`).
+fn prometheus_value(v: f64) -> String {
+    if v.is_nan() {
+        "NaN".to_string()
+    } else if v.is_infinite() {
+        if v > 0.0 { "+Inf" } else { "-Inf" }.to_string()
+    } else {
+        format!("{:?}", v)
+    }
+}
+
+/// Fit `s` into `width` characters, marking truncation with `...`.
+fn truncate_cell(s: &str, width: usize) -> String {
+    if s.chars().count() <= width {
+        s.to_string()
+    } else {
+        let kept: String = s.chars().take(width.saturating_sub(3)).collect();
+        format!("{}...", kept)
+    }
+}
+
+/// Render results as an aligned plain-text table for CLI output.
+///
+/// Columns are NodeId, Region, AssetType, MassAvoided, and Karma; text cells
+/// are truncated to their column width and numbers are right-aligned with
+/// three decimals, widening a numeric column to fit its longest value. An
+/// empty slice renders as `no nodes`.
+pub fn format_summary_table(results: &[(CpvmNodeMeta, EcoImpactResult)]) -> String {
+    const ID_W: usize = 16;
+    const REGION_W: usize = 12;
+    const ASSET_W: usize = 16;
+    const NUM_W: usize = 14;
+
+    if results.is_empty() {
+        return "no nodes\n".to_string();
+    }
+    let masses: Vec<String> = results
+        .iter()
+        .map(|(_, r)| format!("{:.3}", r.mass_avoided))
+        .collect();
+    let karmas: Vec<String> = results
+        .iter()
+        .map(|(_, r)| format!("{:.3}", r.karma_gain))
+        .collect();
+    let width = |cells: &[String]| cells.iter().map(String::len).fold(NUM_W, usize::max);
+    let (mass_w, karma_w) = (width(&masses), width(&karmas));
+
+    let mut out = format!(
+        "{:<ID_W$} {:<REGION_W$} {:<ASSET_W$} {:>mass_w$} {:>karma_w$}\n",
+        "NodeId", "Region", "AssetType", "MassAvoided", "Karma"
+    );
+    out.push_str(&"-".repeat(ID_W + REGION_W + ASSET_W + mass_w + karma_w + 4));
+    out.push('\n');
+    for (((meta, _), mass), karma) in results.iter().zip(&masses).zip(&karmas) {
+        out.push_str(&format!(
+            "{:<ID_W$} {:<REGION_W$} {:<ASSET_W$} {:>mass_w$} {:>karma_w$}\n",
+            truncate_cell(&meta.node_id.0, ID_W),
+            truncate_cell(&meta.region.0, REGION_W),
+            truncate_cell(meta.asset_type.as_canonical_str(), ASSET_W),
+            mass,
+            karma,
+        ));
+    }
+    out
+}
+
+/// Render run metrics in the Prometheus text exposition format.
+///
+/// Emits per-node `cpvm_karma_gain` and `cpvm_mass_avoided` gauges labelled
+/// by `node` and `region`, plus an aggregate `cpvm_total_karma`.
+pub fn render_prometheus(results: &[(CpvmNodeMeta, EcoImpactResult)]) -> String {
+    let labels: Vec<String> = results
+        .iter()
+        .map(|(meta, _)| {
+            format!(
+                "node=\"{}\",region=\"{}\"",
+                escape_prometheus_label(&meta.node_id.0),
+                escape_prometheus_label(&meta.region.0)
+            )
+        })
+        .collect();
+
+    let mut out = String::new();
+    type Extract = fn(&EcoImpactResult) -> f64;
+    let metrics: [(&str, Extract); 2] = [
+        ("cpvm_karma_gain", |r| r.karma_gain),
+        ("cpvm_mass_avoided", |r| r.mass_avoided),
+    ];
+    for (name, value) in metrics {
+        out.push_str(&format!("# TYPE {} gauge\n", name));
+        for ((_, result), labels) in results.iter().zip(&labels) {
+            out.push_str(&format!(
+                "{}{{{}}} {}\n",
+                name,
+                labels,
+                prometheus_value(value(result))
+            ));
+        }
+    }
+    let total: f64 = results.iter().map(|(_, r)| r.karma_gain).sum();
+    out.push_str("# TYPE cpvm_total_karma gauge\n");
+    out.push_str(&format!("cpvm_total_karma {}\n", prometheus_value(total)));
+    out
+}
+
+/// Karma earned per unit of avoided mass; `None` when no mass was avoided.
+pub fn karma_efficiency(result: &EcoImpactResult) -> Option<f64> {
+    if result.mass_avoided == 0.0 {
+        None
+    } else {
+        Some(result.karma_gain / result.mass_avoided)
+    }
+}
+
+/// Nodes ranked by descending [`karma_efficiency`]; zero-mass nodes are
+/// omitted.
+pub fn rank_by_efficiency(results: &[(NodeId, EcoImpactResult)]) -> Vec<(NodeId, f64)> {
+    let mut ranked: Vec<(NodeId, f64)> = results
+        .iter()
+        .filter_map(|(id, r)| karma_efficiency(r).map(|e| (id.clone(), e)))
+        .collect();
+    ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+    ranked
+}
+
+/// Optional: small smoke test demonstrating loading and evaluation.
+///
+/// This is intentionally simple and can be moved into a proper test harness
+/// in a larger workspace.
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_csv_line_basic() {
+        let line = "A,B,C";
+        let f = split_csv_line(line);
+        assert_eq!(f, vec!["A", "B", "C"]);
+    }
+
+    #[test]
+    fn test_split_csv_line_quotes() {
+        let line = "A,\"B, with comma\",C";
+        let f = split_csv_line(line);
+        assert_eq!(f, vec!["A", "B, with comma", "C"]);
+    }
+
+    #[test]
+    fn test_split_csv_line_escaped_quotes() {
+        let line = "A,\"contains \"\"PFAS\"\" limit\",C";
+        let f = split_csv_line(line);
+        assert_eq!(f, vec!["A", "contains \"PFAS\" limit", "C"]);
+    }
+
+    #[test]
+    fn test_split_csv_line_leading_trailing_escaped_quotes() {
+        let line = "\"\"\"PFBS\"\"\",\"ends with \"\"\"";
+        let f = split_csv_line(line);
+        assert_eq!(f, vec!["\"PFBS\"", "ends with \""]);
+    }
+
+    #[test]
+    fn test_split_csv_line_escaped_quote_at_field_start() {
+        let line = "A,\"\"\"quoted\"\" start\"";
+        let f = split_csv_line(line);
+        assert_eq!(f, vec!["A", "\"quoted\" start"]);
+    }
+
+    #[test]
+    fn test_split_csv_line_empty_quoted_field() {
+        assert_eq!(split_csv_line("A,\"\",C"), vec!["A", "", "C"]);
+        assert_eq!(split_csv_line("A,\"\""), vec!["A", ""]);
+        assert_eq!(split_csv_line("\"\""), vec![""]);
+    }
+
+    #[test]
+    fn test_convert_concentration() {
+        let ng = ConcentrationUnit::NgPerL;
+        let mg = ConcentrationUnit::MgPerL;
+        let mpn = ConcentrationUnit::MpnPer100mL;
+
+        let v = convert_concentration(3.9e6, &ng, &mg).unwrap();
+        assert!((v - 3.9).abs() < 1e-9);
+        let v = convert_concentration(0.1, &mg, &ng).unwrap();
+        assert!((v - 1.0e5).abs() < 1e-6);
+        assert_eq!(convert_concentration(410.0, &mpn, &mpn), Some(410.0));
+        assert_eq!(convert_concentration(410.0, &mpn, &mg), None);
+        assert_eq!(
+            convert_concentration(1.0, &ConcentrationUnit::Other("ppm".to_string()), &mg),
+            None
+        );
+    }
+
+    const SHARD_HEADER: &str = "node_id,asset_type,waterbody,region,cpvm_profile,cin_baseline,\
+cin_unit,q_avg,q_unit,horizon_s,ecoimpactscore,karma_per_unit,notes";
+
+    fn write_temp_shard(name: &str, contents: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("cpvm_{}_{}.csv", name, std::process::id()));
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_load_shard_by_column_name() {
+        let contents = format!(
+            "{}\nLP-1,Reservoir,Lake Pleasant,AZ,PFAS_V1,3.9,ng/L,50.0,m3/s,3600,0.88,1.0e6,{}\n",
+            SHARD_HEADER, "\"a, b\""
+        );
+        let path = write_temp_shard("by_name", &contents);
+        let nodes = load_cpvm_nodes_from_csv(path.to_str().unwrap()).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(nodes[0].node_id, NodeId("LP-1".to_string()));
+        assert_eq!(nodes[0].asset_type, AssetType::Reservoir);
+        assert_eq!(nodes[0].q_avg, 50.0);
+        assert_eq!(nodes[0].notes, "a, b");
+    }
+
+    #[test]
+    fn test_load_shard_reordered_columns() {
+        let contents = "karma_per_unit,node_id,q_avg,q_unit,asset_type,waterbody,region,\
+cpvm_profile,cin_unit,cin_baseline,horizon_s,ecoimpactscore,extra\n\
+2.0e6,GILA-07,5.0,m3/s,RiverReach,Gila River,AZ,E_COLI_V1,MPN/100mL,600,900,0.92,x\n";
+        let path = write_temp_shard("reordered", contents);
+        let nodes = load_cpvm_nodes_from_csv(path.to_str().unwrap()).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        let n = &nodes[0];
+        assert_eq!(n.node_id, NodeId("GILA-07".to_string()));
+        assert_eq!(n.asset_type, AssetType::RiverReach);
+        assert_eq!(n.cin_baseline, 600.0);
+        assert_eq!(n.cin_unit, ConcentrationUnit::MpnPer100mL);
+        assert_eq!(n.q_avg, 5.0);
+        assert_eq!(n.horizon_s, 900.0);
+        assert_eq!(n.karma_per_unit, 2.0e6);
+        assert_eq!(n.notes, "");
+    }
+
+    #[test]
+    fn test_load_shard_missing_column() {
+        let contents = "node_id,asset_type,waterbody,region,cpvm_profile,cin_baseline,\
+cin_unit,q_avg,q_unit,ecoimpactscore,karma_per_unit\n";
+        let path = write_temp_shard("missing_col", contents);
+        let err = load_cpvm_nodes_from_csv(path.to_str().unwrap()).unwrap_err();
+        std::fs::remove_file(&path).ok();
+
+        assert!(err.to_string().contains("horizon_s"));
+    }
+
+    #[test]
+    fn test_node_reader_streams_rows() {
+        let contents = [
+            SHARD_HEADER,
+            "A,Plant,W,R,P,1.0,mg/L,1.0,m3/s,60,0.5,1.0,",
+            "",
+            "B,Basin,W,R,P,2.0,mg/L,1.0,m3/s,60,0.5,1.0,",
+            "C,Basin,W,R,P,oops,mg/L,1.0,m3/s,60,0.5,1.0,",
+        ]
+        .join("\n");
+        let path = write_temp_shard("reader", &contents);
+        let reader = CpvmNodeReader::open(path.to_str().unwrap()).unwrap();
+        let rows: Vec<_> = reader.collect();
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[0].as_ref().unwrap().node_id, NodeId("A".to_string()));
+        assert_eq!(rows[1].as_ref().unwrap().node_id, NodeId("B".to_string()));
+        assert!(rows[2].is_err());
+    }
+
+    #[test]
+    fn test_load_from_byte_slice() {
+        let bytes: &[u8] = b"node_id,asset_type,waterbody,region,cpvm_profile,cin_baseline,\
+cin_unit,q_avg,q_unit,horizon_s,ecoimpactscore,karma_per_unit,notes
+PHX-TP-01,Plant,Phoenix WTP,Central AZ,NUTRIENT_TP_V1,0.10,mg/L,3.0,m3/s,1800,0.70,5.0e5,TP
+";
+        let nodes = load_cpvm_nodes_from_reader(bytes).unwrap();
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(nodes[0].node_id, NodeId("PHX-TP-01".to_string()));
+        assert_eq!(nodes[0].cin_baseline, 0.10);
+
+        let empty: &[u8] = b"";
+        assert!(load_cpvm_nodes_from_reader(empty).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_write_csv_round_trip() {
+        let mut quoted = test_meta(50.0, FlowUnit::MGD);
+        quoted.node_id = NodeId("LP, \"north\"".to_string());
+        quoted.asset_type = AssetType::Other("Wetland".to_string());
+        quoted.cin_unit = ConcentrationUnit::NgPerL;
+        quoted.notes = "PFBS 3.9 ng/L, \"low\" but monitored, \"\"".to_string();
+        let nodes = vec![test_meta(1.5, FlowUnit::M3PerS), quoted];
+
+        let mut buf = Vec::new();
+        write_cpvm_nodes_to_csv(&mut buf, &nodes).unwrap();
+        let loaded = load_cpvm_nodes_from_reader(buf.as_slice()).unwrap();
+        assert_eq!(loaded, nodes);
+
+        let mut again = Vec::new();
+        write_cpvm_nodes_to_csv(&mut again, &loaded).unwrap();
+        assert_eq!(again, buf);
+    }
+
+    #[test]
+    fn test_evaluate_batch() {
+        let configs = vec![
+            bind_cpvm_config(test_meta(1.0, FlowUnit::M3PerS), 5.0, 10.0, 100.0),
+            bind_cpvm_config(test_meta(2.0, FlowUnit::M3PerS), 5.0, 10.0, 100.0),
+        ];
+        let batch = evaluate_ecoimpact_batch(&configs, &[3.0, 4.0]).unwrap();
+
+        assert_eq!(batch.results.len(), 2);
+        let first = evaluate_ecoimpact_for_node(&configs[0], 3.0);
+        let second = evaluate_ecoimpact_for_node(&configs[1], 4.0);
+        assert_eq!(batch.results[0].karma_gain, first.karma_gain);
+        assert_eq!(batch.results[1].karma_gain, second.karma_gain);
+        assert!((batch.total_karma - (first.karma_gain + second.karma_gain)).abs() < 1e-6);
+
+        assert!(evaluate_ecoimpact_batch(&configs, &[3.0]).is_err());
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_evaluate_batch_par_matches_serial() {
+        let configs: Vec<_> = (0..10_000)
+            .map(|i| {
+                let mut meta = test_meta(0.5 + (i % 97) as f64 * 0.25, FlowUnit::M3PerS);
+                meta.node_id = NodeId(format!("SYN-{}", i));
+                meta.cin_baseline = 5.0 + (i % 13) as f64;
+                meta.ecoimpactscore = (i % 11) as f64 / 10.0;
+                bind_cpvm_config(meta, 5.0, 10.0, 100.0)
+            })
+            .collect();
+        let couts: Vec<f64> = (0..10_000).map(|i| (i % 17) as f64 * 0.5).collect();
+
+        let serial = evaluate_ecoimpact_batch(&configs, &couts).unwrap();
+        let par = evaluate_ecoimpact_batch_par(&configs, &couts).unwrap();
+
+        assert_eq!(serial.results.len(), par.results.len());
+        for (a, b) in serial.results.iter().zip(&par.results) {
+            assert_eq!(a.mass_avoided.to_bits(), b.mass_avoided.to_bits());
+            assert_eq!(a.karma_gain.to_bits(), b.karma_gain.to_bits());
+        }
+        assert_eq!(serial.total_karma.to_bits(), par.total_karma.to_bits());
+        assert!(evaluate_ecoimpact_batch_par(&configs, &couts[1..]).is_err());
+    }
+
+    #[test]
+    fn test_compute_mass_avoided() {
+        let m = compute_mass_avoided(10.0, 5.0, 2.0, 100.0);
+        assert!((m - 1000.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_evaluate_ecoimpact_for_node() {
+        let meta = CpvmNodeMeta {
+            node_id: NodeId("TEST-NODE".to_string()),
+            asset_type: AssetType::Plant,
+            waterbody: Waterbody::from("TestRiver"),
+            region: Region::from("TestRegion"),
+            cpvm_profile: "TEST_PROFILE".to_string(),
+            cin_baseline: 10.0,
+            cin_unit: ConcentrationUnit::MgPerL,
+            q_avg: 1.0,
+            q_unit: FlowUnit::M3PerS,
+            horizon_s: 3600.0,
+            ecoimpactscore: 0.8,
+            karma_per_unit: 1.0e3,
+            notes: "Test node".to_string(),
+            tags: Vec::new(),
+            secondary: None,
+            residence_time_s: None,
+            lat: None,
+            lon: None,
+        };
+
+        let cfg = bind_cpvm_config(meta, 5.0, 10.0, 100.0);
+        let res = evaluate_ecoimpact_for_node(&cfg, 3.0);
+
+        assert!(res.mass_avoided > 0.0);
+        assert!(res.karma_gain > 0.0);
+        assert!(res.ecoimpactscore <= 1.0);
+    }
+
+    fn test_meta(q_avg: f64, q_unit: FlowUnit) -> CpvmNodeMeta {
+        CpvmNodeMeta {
+            node_id: NodeId("TEST-NODE".to_string()),
+            asset_type: AssetType::Plant,
+            waterbody: Waterbody::from("TestRiver"),
+            region: Region::from("TestRegion"),
+            cpvm_profile: "TEST_PROFILE".to_string(),
+            cin_baseline: 10.0,
+            cin_unit: ConcentrationUnit::MgPerL,
+            q_avg,
+            q_unit,
+            horizon_s: 3600.0,
+            ecoimpactscore: 0.8,
+            karma_per_unit: 1.0e3,
+            notes: String::new(),
+            tags: Vec::new(),
+            secondary: None,
+            residence_time_s: None,
+            lat: None,
+            lon: None,
+        }
+    }
+
+    #[test]
+    fn test_flow_unit_from_str() {
+        assert_eq!(FlowUnit::from_str("L/s"), FlowUnit::LPerS);
+        assert_eq!(FlowUnit::from_str(" MGD "), FlowUnit::MGD);
+        assert_eq!(
+            FlowUnit::from_str("cfs"),
+            FlowUnit::Other("cfs".to_string())
+        );
+        assert_eq!(
+            FlowUnit::Other("cfs".to_string()).to_m3_per_s_factor(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_evaluate_mgd_matches_m3_per_s() {
+        let mgd = bind_cpvm_config(test_meta(10.0, FlowUnit::MGD), 5.0, 10.0, 100.0);
+        let si = bind_cpvm_config(
+            test_meta(10.0 * M3_PER_S_PER_MGD, FlowUnit::M3PerS),
+            5.0,
+            10.0,
+            100.0,
+        );
+        let a = evaluate_ecoimpact_for_node(&mgd, 3.0);
+        let b = evaluate_ecoimpact_for_node(&si, 3.0);
+        assert!((a.mass_avoided - b.mass_avoided).abs() < 1e-9);
+        assert!((10.0 * M3_PER_S_PER_MGD - 0.438_126).abs() < 1e-6);
+
+        let lps = bind_cpvm_config(test_meta(1000.0, FlowUnit::LPerS), 5.0, 10.0, 100.0);
+        let one = bind_cpvm_config(test_meta(1.0, FlowUnit::M3PerS), 5.0, 10.0, 100.0);
+        let a = evaluate_ecoimpact_for_node(&lps, 3.0);
+        let b = evaluate_ecoimpact_for_node(&one, 3.0);
+        assert!((a.mass_avoided - b.mass_avoided).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_try_evaluate_converts_cout_unit() {
+        let mut meta = test_meta(1.0, FlowUnit::M3PerS);
+        meta.cin_unit = ConcentrationUnit::NgPerL;
+        meta.cin_baseline = 3.9;
+        let cfg = bind_cpvm_config(meta, 4.0, 10.0, 100.0);
+
+        let a = try_evaluate_ecoimpact_for_node(&cfg, 2.0e-6, &ConcentrationUnit::MgPerL).unwrap();
+        let b = try_evaluate_ecoimpact_for_node(&cfg, 2.0, &ConcentrationUnit::NgPerL).unwrap();
+        assert!((a.mass_avoided - b.mass_avoided).abs() < 1e-9);
+        assert!((a.mass_avoided - 1.9 * 3600.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_try_evaluate_rejects_unit_mismatch() {
+        let mut meta = test_meta(1.0, FlowUnit::M3PerS);
+        meta.cin_unit = ConcentrationUnit::NgPerL;
+        let cfg = bind_cpvm_config(meta, 4.0, 10.0, 100.0);
+
+        let err = try_evaluate_ecoimpact_for_node(&cfg, 200.0, &ConcentrationUnit::MpnPer100mL)
+            .unwrap_err();
+        match err {
+            CpvmLinkerError::Parse(msg) => {
+                assert!(msg.contains("TEST-NODE"));
+                assert!(msg.contains("MpnPer100mL"));
+            }
+            other => panic!("unexpected error: {}", other),
+        }
+    }
+
+    #[test]
+    fn test_evaluate_unknown_flow_unit_yields_zero() {
+        let cfg = bind_cpvm_config(
+            test_meta(10.0, FlowUnit::Other("cfs".to_string())),
+            5.0,
+            10.0,
+            100.0,
+        );
+        let res = evaluate_ecoimpact_for_node(&cfg, 3.0);
+        assert_eq!(res.mass_avoided, 0.0);
+        assert_eq!(res.karma_gain, 0.0);
+        assert!(try_evaluate_ecoimpact_for_node(&cfg, 3.0, &ConcentrationUnit::MgPerL).is_err());
+    }
+
+    fn test_result(mass_avoided: f64, ecoimpactscore: f64, karma_gain: f64) -> EcoImpactResult {
+        EcoImpactResult {
+            mass_avoided,
+            ecoimpactscore,
+            karma_gain,
+            mass_unit: MassUnit::G,
+            horizon_s: 100.0,
+            q_m3_per_s: 1.0,
+        }
+    }
+
+    #[test]
+    fn test_aggregate_by_region() {
+        let mut north = test_meta(1.0, FlowUnit::M3PerS);
+        north.region = Region::from("North");
+        let mut south = test_meta(1.0, FlowUnit::M3PerS);
+        south.region = Region::from("South");
+
+        let results = vec![
+            (north.clone(), test_result(10.0, 0.4, 100.0)),
+            (north, test_result(30.0, 0.8, 300.0)),
+            (south, test_result(5.0, 0.5, 50.0)),
+        ];
+        let by_region = aggregate_by_region(&results);
+
+        assert_eq!(by_region.len(), 2);
+        let n = &by_region[&Region::from("North")];
+        assert_eq!(n.node_count, 2);
+        assert_eq!(n.total_mass_avoided, 40.0);
+        assert_eq!(n.total_karma_gain, 400.0);
+        assert!((n.mean_ecoimpactscore - 0.6).abs() < 1e-12);
+        let s = &by_region[&Region::from("South")];
+        assert_eq!(s.node_count, 1);
+        assert_eq!(s.mean_ecoimpactscore, 0.5);
+    }
+
+    #[test]
+    fn test_aggregate_by_waterbody_asset() {
+        let node = |waterbody: &str, asset_type: AssetType| {
+            let mut meta = test_meta(1.0, FlowUnit::M3PerS);
+            meta.waterbody = Waterbody::from(waterbody);
+            meta.asset_type = asset_type;
+            meta
+        };
+        let wetland = AssetType::Other("Wetland".to_string());
+        let results = vec![
+            (
+                node("Gila", AssetType::RiverReach),
+                test_result(1.0, 0.5, 10.0),
+            ),
+            (
+                node("Gila", AssetType::RiverReach),
+                test_result(2.0, 0.5, 20.0),
+            ),
+            (node("Gila", wetland.clone()), test_result(4.0, 0.5, 40.0)),
+            (
+                node("Lake Pleasant", AssetType::Reservoir),
+                test_result(8.0, 0.5, 80.0),
+            ),
+            (
+                node("Lake Pleasant", wetland.clone()),
+                test_result(16.0, 0.5, 160.0),
+            ),
+        ];
+        let cells = aggregate_by_waterbody_asset(&results);
+
+        assert_eq!(cells.len(), 4);
+        let reach = &cells[&(Waterbody::from("Gila"), AssetType::RiverReach)];
+        assert_eq!(reach.node_count, 2);
+        assert_eq!(reach.total_mass_avoided, 3.0);
+        assert_eq!(reach.total_karma_gain, 30.0);
+        let gila_wetland = &cells[&(Waterbody::from("Gila"), wetland.clone())];
+        assert_eq!(gila_wetland.node_count, 1);
+        assert_eq!(gila_wetland.total_karma_gain, 40.0);
+        let lp_wetland = &cells[&(Waterbody::from("Lake Pleasant"), wetland)];
+        assert_eq!(lp_wetland.total_mass_avoided, 16.0);
+        let lp_res = &cells[&(Waterbody::from("Lake Pleasant"), AssetType::Reservoir)];
+        assert_eq!(lp_res.node_count, 1);
+    }
+
+    #[test]
+    fn test_validate_node_meta() {
+        assert!(validate_node_meta(&test_meta(1.0, FlowUnit::M3PerS)).is_ok());
+
+        type Mutation = fn(&mut CpvmNodeMeta);
+        let cases: [(&str, Mutation); 6] = [
+            ("q_avg", |m| m.q_avg = -1.0),
+            ("horizon_s", |m| m.horizon_s = 0.0),
+            ("ecoimpactscore", |m| m.ecoimpactscore = 1.5),
+            ("ecoimpactscore", |m| m.ecoimpactscore = f64::NAN),
+            ("cin_baseline", |m| m.cin_baseline = -0.1),
+            ("karma_per_unit", |m| m.karma_per_unit = f64::INFINITY),
+        ];
+        for (field, mutate) in cases {
+            let mut meta = test_meta(1.0, FlowUnit::M3PerS);
+            mutate(&mut meta);
+            let msg = validate_node_meta(&meta).unwrap_err().to_string();
+            assert!(msg.contains(field), "{}", msg);
+            assert!(msg.contains("TEST-NODE"), "{}", msg);
+        }
+    }
+
+    #[test]
+    fn test_load_validated_aggregates_failures() {
+        let contents = [
+            SHARD_HEADER,
+            "OK-1,Plant,W,R,P,1.0,mg/L,1.0,m3/s,60,0.5,1.0,",
+            "BAD-1,Plant,W,R,P,1.0,mg/L,-1.0,m3/s,60,0.5,1.0,",
+            "BAD-2,Plant,W,R,P,1.0,mg/L,1.0,m3/s,0,0.5,1.0,",
+        ]
+        .join("\n");
+        let path = write_temp_shard("validated", &contents);
+        let err = load_cpvm_nodes_from_csv_validated(path.to_str().unwrap()).unwrap_err();
+        std::fs::remove_file(&path).ok();
+
+        let msg = err.to_string();
+        assert!(msg.contains("BAD-1") && msg.contains("q_avg"), "{}", msg);
+        assert!(
+            msg.contains("BAD-2") && msg.contains("horizon_s"),
+            "{}",
+            msg
+        );
+        assert!(!msg.contains("OK-1"), "{}", msg);
+    }
+
+    #[test]
+    fn test_load_collect_errors() {
+        let contents = [
+            SHARD_HEADER,
+            "A,Plant,W,R,P,1.0,mg/L,1.0,m3/s,60,0.5,1.0,",
+            "B,Plant,W,R,P,x,mg/L,1.0,m3/s,60,0.5,1.0,",
+            "",
+            "C,Plant,W,R,P,1.0,mg/L,1.0,m3/s,60,0.5,1.0,",
+            "D,Plant,too,short",
+        ]
+        .join("\n");
+        let path = write_temp_shard("collect_errors", &contents);
+        let (nodes, errors) = load_cpvm_nodes_collect_errors(path.to_str().unwrap());
+        std::fs::remove_file(&path).ok();
+
+        let ids: Vec<_> = nodes.iter().map(|n| n.node_id.0.as_str()).collect();
+        assert_eq!(ids, vec!["A", "C"]);
+        let lines: Vec<_> = errors.iter().map(|(line, _)| *line).collect();
+        assert_eq!(lines, vec![3, 6]);
+        assert!(errors[1].1.to_string().contains("Line 6"));
+
+        let (nodes, errors) = load_cpvm_nodes_collect_errors("/nonexistent/shard.csv");
+        assert!(nodes.is_empty());
+        assert_eq!(errors[0].0, 0);
+    }
+
+    #[test]
+    fn test_node_meta_builder() {
+        let meta = CpvmNodeMeta::builder()
+            .node_id("TEST-NODE")
+            .asset_type(AssetType::Plant)
+            .waterbody("TestRiver")
+            .region("TestRegion")
+            .cpvm_profile("TEST_PROFILE")
+            .cin_baseline(10.0)
+            .cin_unit(ConcentrationUnit::MgPerL)
+            .q_avg(2.0)
+            .q_unit(FlowUnit::M3PerS)
+            .horizon_s(3600.0)
+            .ecoimpactscore(0.8)
+            .karma_per_unit(1.0e3)
+            .build()
+            .unwrap();
+        assert_eq!(meta, test_meta(2.0, FlowUnit::M3PerS));
+
+        let minimal = CpvmNodeMeta::builder()
+            .node_id("MIN")
+            .asset_type(AssetType::Basin)
+            .cin_unit(ConcentrationUnit::MgPerL)
+            .q_unit(FlowUnit::M3PerS)
+            .horizon_s(60.0)
+            .build()
+            .unwrap();
+        assert_eq!(minimal.notes, "");
+        assert_eq!(minimal.ecoimpactscore, 0.0);
+    }
+
+    #[test]
+    fn test_node_meta_builder_errors() {
+        let err = CpvmNodeMeta::builder()
+            .node_id("NO-Q-UNIT")
+            .asset_type(AssetType::Plant)
+            .cin_unit(ConcentrationUnit::MgPerL)
+            .horizon_s(60.0)
+            .build()
+            .unwrap_err();
+        assert!(err.to_string().contains("q_unit"));
+
+        let err = CpvmNodeMeta::builder().build().unwrap_err();
+        assert!(err.to_string().contains("node_id"));
+
+        let err = CpvmNodeMeta::builder()
+            .node_id("BAD-SCORE")
+            .asset_type(AssetType::Plant)
+            .cin_unit(ConcentrationUnit::MgPerL)
+            .q_unit(FlowUnit::M3PerS)
+            .horizon_s(60.0)
+            .ecoimpactscore(2.0)
+            .build()
+            .unwrap_err();
+        assert!(err.to_string().contains("ecoimpactscore"));
+    }
+
+    #[test]
+    fn test_karma_ledger() {
+        let lp = NodeId("CAP-LP".to_string());
+        let gila = NodeId("GILA-07".to_string());
+        let mut ledger = KarmaLedger::new();
+
+        ledger.credit(&lp, 100.0, 0.0);
+        ledger.credit(&gila, 40.0, 10.0);
+        ledger.credit(&lp, 25.0, 20.0);
+        ledger.credit(&lp, -30.0, 30.0);
+
+        assert_eq!(ledger.balance(&lp), 95.0);
+        assert_eq!(ledger.balance(&gila), 40.0);
+        assert_eq!(ledger.balance(&NodeId("UNKNOWN".to_string())), 0.0);
+        assert_eq!(ledger.total_balance(), 135.0);
+
+        let txns = ledger.transactions();
+        assert_eq!(txns.len(), 4);
+        assert_eq!(
+            txns[3],
+            KarmaTxn {
+                node: lp,
+                gain: -30.0,
+                timestamp_s: 30.0
+            }
+        );
+    }
+
+    #[test]
+    fn test_bind_with_profile_overrides() {
+        let defaults = SafetyDefaults {
+            cref: 5.0,
+            lambda_clf: 10.0,
+            mu_cbf: 100.0,
+        };
+        let mut overrides = HashMap::new();
+        overrides.insert(
+            "PFAS_STRICT".to_string(),
+            SafetyDefaults {
+                cref: 2.0,
+                ..defaults
+            },
+        );
+
+        let plain = test_meta(1.0, FlowUnit::M3PerS);
+        let also_plain = test_meta(2.0, FlowUnit::M3PerS);
+        let mut strict = test_meta(1.0, FlowUnit::M3PerS);
+        strict.cpvm_profile = "PFAS_STRICT".to_string();
+
+        let a = bind_cpvm_config_with_overrides(plain, defaults, &overrides);
+        let b = bind_cpvm_config_with_overrides(also_plain, defaults, &overrides);
+        let c = bind_cpvm_config_with_overrides(strict, defaults, &overrides);
+
+        assert_eq!(a.safety.cref, 5.0);
+        assert_eq!(b.safety.cref, 5.0);
+        assert_eq!(a.safety.safe_threshold, 5.0);
+        assert_eq!(c.safety.cref, 2.0);
+        assert_eq!(c.safety.safe_threshold, 2.0);
+        assert_eq!(c.safety.mu_cbf, 100.0);
+    }
+
+    #[test]
+    fn test_regulatory_safety_config() {
+        let mut limits = RegulatoryLimits::default();
+        limits.by_profile.insert(
+            "PFAS_PFBS_LP_v1".to_string(),
+            ProfileLimits {
+                epa: Some(2000.0),
+                eu: Some(100.0),
+                who: None,
+            },
+        );
+
+        let mut pfas = test_meta(1.0, FlowUnit::M3PerS);
+        pfas.cpvm_profile = "PFAS_PFBS_LP_v1".to_string();
+        pfas.cin_baseline = 3.9;
+        let cfg = derive_safety_config_regulatory(&pfas, &limits, 10.0, 100.0);
+        assert_eq!(cfg.safe_threshold, 100.0);
+        assert_eq!(cfg.lambda_clf, 10.0);
+        assert_eq!(cfg.mu_cbf, 100.0);
+
+        let unlisted = test_meta(1.0, FlowUnit::M3PerS);
+        let cfg = derive_safety_config_regulatory(&unlisted, &limits, 10.0, 100.0);
+        assert_eq!(cfg.safe_threshold, unlisted.cin_baseline);
+
+        assert_eq!(ProfileLimits::default().binding_limit(), None);
+    }
+
+    #[test]
+    fn test_clf_residual() {
+        let cfg = bind_cpvm_config(test_meta(1.0, FlowUnit::M3PerS), 5.0, 10.0, 100.0);
+        assert_eq!(cfg.safety.safe_threshold, 5.0);
+
+        assert_eq!(clf_residual(&cfg, 0.0), 0.0);
+        assert_eq!(clf_residual(&cfg, 5.0), 0.0);
+        // 10 * ((7.5 - 5) / 5)^2 = 2.5
+        assert!((clf_residual(&cfg, 7.5) - 2.5).abs() < 1e-12);
+
+        let mut last = 0.0;
+        for i in 1..50 {
+            let r = clf_residual(&cfg, 5.0 + i as f64 * 0.1);
+            assert!(r > last);
+            last = r;
+        }
+    }
+
+    #[test]
+    fn test_cbf_residual_and_safety_cost() {
+        let cfg = bind_cpvm_config(test_meta(1.0, FlowUnit::M3PerS), 5.0, 10.0, 100.0);
+
+        // -100 * ln(5 - 4) = 0
+        assert!(cbf_residual(&cfg, 4.0).abs() < 1e-12);
+        assert!(cbf_residual(&cfg, 4.999) > cbf_residual(&cfg, 4.9));
+        assert_eq!(cbf_residual(&cfg, 5.0), f64::INFINITY);
+        assert_eq!(cbf_residual(&cfg, 6.0), f64::INFINITY);
+
+        for cout in [0.0, 1.0, 2.5, 4.0, 4.99] {
+            assert!(safety_cost(&cfg, cout).is_finite());
+        }
+        for cout in [5.0, 5.01, 10.0] {
+            assert_eq!(safety_cost(&cfg, cout), f64::INFINITY);
+        }
+    }
+
+    #[test]
+    fn test_optimize_cout_respects_barrier() {
+        let cfg = bind_cpvm_config(test_meta(1.0, FlowUnit::M3PerS), 5.0, 10.0, 100.0);
+        let (cout, res) = optimize_cout(&cfg, 0.0, 10.0, 100);
+
+        assert!(cout < cfg.safety.safe_threshold);
+        assert_eq!(
+            res.karma_gain,
+            evaluate_ecoimpact_for_node(&cfg, cout).karma_gain
+        );
+        // Karma decreases in C_out and the barrier only adds cost, so the
+        // lowest candidate wins.
+        assert_eq!(cout, 0.0);
+
+        // A grid straddling the threshold must never pick an infeasible point,
+        // even when the first candidate is itself infeasible.
+        let (cout, _) = optimize_cout(&cfg, 4.0, 6.0, 40);
+        assert!(cout < cfg.safety.safe_threshold);
+        let (cout, res) = optimize_cout(&cfg, 6.0, 3.0, 30);
+        assert!(cout < cfg.safety.safe_threshold);
+        assert!(res.karma_gain > 0.0);
+    }
+
+    #[test]
+    fn test_compute_mass_avoided_series() {
+        let uniform = [(0.0, 2.0), (40.0, 2.0), (100.0, 2.0)];
+        let m = compute_mass_avoided_series(10.0, 5.0, &uniform);
+        assert!((m - compute_mass_avoided(10.0, 5.0, 2.0, 100.0)).abs() < 1e-9);
+
+        // Linear ramp 0 → 4 m3/s over 100 s: mean Q = 2.
+        let ramp = [(0.0, 0.0), (100.0, 4.0)];
+        assert!((compute_mass_avoided_series(10.0, 5.0, &ramp) - 1000.0).abs() < 1e-9);
+
+        assert_eq!(compute_mass_avoided_series(5.0, 10.0, &uniform), 0.0);
+        assert_eq!(compute_mass_avoided_series(10.0, 5.0, &[(0.0, 2.0)]), 0.0);
+    }
+
+    #[test]
+    fn test_compute_mass_avoided_cin_series() {
+        let steady = [(0.0, 10.0), (50.0, 10.0), (100.0, 10.0)];
+        let m = compute_mass_avoided_cin_series(&steady, 5.0, 2.0);
+        assert!((m - compute_mass_avoided(10.0, 5.0, 2.0, 100.0)).abs() < 1e-9);
+
+        // Storm pulse: C_in dips below C_out at t=0, clamped per sample.
+        // Loads are 0, 10, 0 over two 10 s intervals -> 100.
+        let pulse = [(0.0, 2.0), (10.0, 15.0), (20.0, 5.0)];
+        assert!((compute_mass_avoided_cin_series(&pulse, 5.0, 1.0) - 100.0).abs() < 1e-9);
+
+        assert_eq!(
+            compute_mass_avoided_cin_series(&[(0.0, 10.0)], 5.0, 2.0),
+            0.0
+        );
+        assert_eq!(compute_mass_avoided_cin_series(&[], 5.0, 2.0), 0.0);
+
+        let backwards = [(0.0, 10.0), (100.0, 10.0), (50.0, 10.0)];
+        let err = try_compute_mass_avoided_cin_series(&backwards, 5.0, 2.0).unwrap_err();
+        assert!(err.to_string().contains("monotonic"));
+        assert_eq!(compute_mass_avoided_cin_series(&backwards, 5.0, 2.0), 0.0);
+    }
+
+    #[test]
+    fn test_filter_nodes() {
+        let node = |id: &str, asset_type: AssetType, region: &str| {
+            let mut meta = test_meta(1.0, FlowUnit::M3PerS);
+            meta.node_id = NodeId(id.to_string());
+            meta.asset_type = asset_type;
+            meta.region = Region::from(region);
+            meta
+        };
+        let nodes = vec![
+            node("A", AssetType::Reservoir, "North"),
+            node("B", AssetType::Plant, "North"),
+            node("C", AssetType::Reservoir, "South"),
+            node("D", AssetType::Other("Wetland".to_string()), "South"),
+            node("E", AssetType::Other("Canal".to_string()), "South"),
+        ];
+        let ids = |v: Vec<&CpvmNodeMeta>| v.iter().map(|n| n.node_id.0.clone()).collect::<Vec<_>>();
+
+        assert_eq!(ids(filter_nodes(&nodes, None, None)).len(), 5);
+        assert_eq!(
+            ids(filter_nodes(&nodes, Some(&AssetType::Reservoir), None)),
+            ["A", "C"]
+        );
+        assert_eq!(
+            ids(filter_nodes(&nodes, None, Some(&Region::from("North")))),
+            ["A", "B"]
+        );
+        assert_eq!(
+            ids(filter_nodes(
+                &nodes,
+                Some(&AssetType::Reservoir),
+                Some(&Region::from("South"))
+            )),
+            ["C"]
+        );
+        let wetland = AssetType::Other("Wetland".to_string());
+        assert_eq!(ids(filter_nodes(&nodes, Some(&wetland), None)), ["D"]);
+        assert!(filter_nodes(&nodes, Some(&AssetType::Basin), None).is_empty());
+    }
+
+    fn test_config(id: &str) -> CpvmNodeConfig {
+        let mut meta = test_meta(1.0, FlowUnit::M3PerS);
+        meta.node_id = NodeId(id.to_string());
+        bind_cpvm_config(meta, 5.0, 10.0, 100.0)
+    }
+
+    #[test]
+    fn test_index_configs_by_id() {
+        let configs = vec![test_config("A"), test_config("B")];
+
+        let by_ref = index_configs_by_id_ref(&configs);
+        assert_eq!(by_ref.len(), 2);
+        assert_eq!(by_ref[&NodeId("B".to_string())].meta.node_id.0, "B");
+        assert_eq!(try_index_configs_by_id_ref(&configs).unwrap().len(), 2);
+
+        let owned = try_index_configs_by_id(configs.clone()).unwrap();
+        assert!(owned.contains_key(&NodeId("A".to_string())));
+        assert_eq!(index_configs_by_id(configs).len(), 2);
+    }
+
+    #[test]
+    fn test_index_configs_rejects_duplicates() {
+        let configs = vec![test_config("A"), test_config("DUP"), test_config("DUP")];
+
+        let err = try_index_configs_by_id_ref(&configs).unwrap_err();
+        assert!(err.to_string().contains("DUP"));
+        let err = try_index_configs_by_id(configs.clone()).unwrap_err();
+        assert!(err.to_string().contains("DUP"));
+        assert_eq!(index_configs_by_id(configs).len(), 2);
+    }
+
+    #[test]
+    fn test_find_duplicate_node_ids() {
+        let contents = [
+            SHARD_HEADER,
+            "LP-1,Reservoir,W,R,P,1.0,mg/L,1.0,m3/s,60,0.5,1.0,first",
+            "GILA-7,RiverReach,W,R,P,1.0,mg/L,1.0,m3/s,60,0.5,1.0,",
+            "LP-1,Reservoir,W,R,P,1.0,mg/L,1.0,m3/s,60,0.5,1.0,bad join",
+        ]
+        .join("\n");
+        let path = write_temp_shard("duplicates", &contents);
+        let nodes = load_cpvm_nodes_from_csv(path.to_str().unwrap()).unwrap();
+        let err = load_cpvm_nodes_from_csv_unique(path.to_str().unwrap()).unwrap_err();
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(
+            find_duplicate_node_ids(&nodes),
+            vec![(NodeId("LP-1".to_string()), 2)]
+        );
+        assert!(err.to_string().contains("LP-1"));
+        assert!(find_duplicate_node_ids(&nodes[..2]).is_empty());
+    }
+
+    #[test]
+    fn test_display_impls() {
+        assert_eq!(NodeId("CAP-LP".to_string()).to_string(), "CAP-LP");
+        assert_eq!(AssetType::RiverReach.to_string(), "RiverReach");
+        assert_eq!(
+            AssetType::Other("Wetland".to_string()).to_string(),
+            "Wetland"
+        );
+        assert_eq!(ConcentrationUnit::MpnPer100mL.to_string(), "MPN/100mL");
+        assert_eq!(FlowUnit::MGD.to_string(), "MGD");
+        assert_eq!(
+            test_result(1234.5, 0.8, 2.5e6).to_string(),
+            "mass=1.2345e3 score=0.800 karma=2.5000e6"
+        );
+    }
+
+    #[test]
+    fn test_summarize_results() {
+        assert_eq!(summarize_results(&[]), ResultStats::default());
+
+        let single = summarize_results(&[test_result(1.0, 0.5, 7.0)]);
+        assert_eq!(single.count, 1);
+        assert_eq!(single.median_karma, 7.0);
+        assert_eq!(single.min_karma, 7.0);
+        assert_eq!(single.max_karma, 7.0);
+        assert_eq!(single.std_dev_karma, 0.0);
+
+        let odd: Vec<_> = [9.0, 1.0, 5.0]
+            .iter()
+            .map(|&k| test_result(1.0, 0.5, k))
+            .collect();
+        let stats = summarize_results(&odd);
+        assert_eq!(stats.median_karma, 5.0);
+        assert_eq!(stats.total_karma, 15.0);
+        assert_eq!(stats.mean_karma, 5.0);
+        assert_eq!(stats.min_karma, 1.0);
+        assert_eq!(stats.max_karma, 9.0);
+
+        let even: Vec<_> = [2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0]
+            .iter()
+            .map(|&k| test_result(1.0, 0.5, k))
+            .collect();
+        let stats = summarize_results(&even);
+        assert_eq!(stats.count, 8);
+        assert_eq!(stats.median_karma, 4.5);
+        assert_eq!(stats.mean_karma, 5.0);
+        assert!((stats.std_dev_karma - 2.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_split_line_with_delimiter() {
+        assert_eq!(
+            split_line_with_delimiter("A\t\"B\twith tab\"\tC", '\t'),
+            vec!["A", "B\twith tab", "C"]
+        );
+        assert_eq!(
+            split_line_with_delimiter("A;\"B; with semicolon\";C,D", ';'),
+            vec!["A", "B; with semicolon", "C,D"]
+        );
+    }
+
+    #[test]
+    fn test_load_with_options_delimiters() {
+        let header_tsv = SHARD_HEADER.replace(',', "\t");
+        let row = "LP-1,Reservoir,Lake Pleasant,AZ,P,3.9,ng/L,50,m3/s,3600,0.88,1e6,\"a\tb\"";
+        let tsv = format!("{}\n{}\n", header_tsv, row.replace(',', "\t"));
+        let path = write_temp_shard("tsv", &tsv);
+        let opts = LoaderOptions {
+            delimiter: '\t',
+            ..LoaderOptions::default()
+        };
+        let nodes = load_cpvm_nodes_with_options(path.to_str().unwrap(), opts).unwrap();
+        std::fs::remove_file(&path).ok();
+        assert_eq!(nodes[0].node_id, NodeId("LP-1".to_string()));
+        assert_eq!(nodes[0].q_avg, 50.0);
+        assert_eq!(nodes[0].notes, "a\tb");
+
+        let ssv = "GILA-7;RiverReach;\"Gila; lower\";AZ;P;600;MPN/100mL;5;m3/s;900;0.92;2e6;x, y\n";
+        let path = write_temp_shard("ssv", ssv);
+        let opts = LoaderOptions {
+            delimiter: ';',
+            has_header: false,
+            ..LoaderOptions::default()
+        };
+        let nodes = load_cpvm_nodes_with_options(path.to_str().unwrap(), opts).unwrap();
+        std::fs::remove_file(&path).ok();
+        assert_eq!(nodes[0].waterbody.0, "Gila; lower");
+        assert_eq!(nodes[0].cin_baseline, 600.0);
+        assert_eq!(nodes[0].notes, "x, y");
+    }
+
+    #[test]
+    fn test_load_strips_utf8_bom() {
+        let row = "LP-1,Reservoir,W,R,P,1.0,mg/L,1.0,m3/s,60,0.5,1.0,";
+        let mut bytes = vec![0xEF, 0xBB, 0xBF];
+        bytes.extend_from_slice(format!("{}\n{}\n", SHARD_HEADER, row).as_bytes());
+        let nodes = load_cpvm_nodes_from_reader(bytes.as_slice()).unwrap();
+        assert_eq!(nodes[0].node_id, NodeId("LP-1".to_string()));
+
+        let mut bytes = vec![0xEF, 0xBB, 0xBF];
+        bytes.extend_from_slice(row.as_bytes());
+        let opts = LoaderOptions {
+            has_header: false,
+            ..LoaderOptions::default()
+        };
+        let nodes: Vec<_> = CpvmNodeReader::with_options(bytes.as_slice(), opts)
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(nodes[0].node_id, NodeId("LP-1".to_string()));
+    }
+
+    #[test]
+    fn test_load_skips_comment_lines() {
+        let contents = [
+            "# source: ADEQ 2024 export",
+            "  # sanitized 2026-01",
+            SHARD_HEADER,
+            "# provenance: CAP",
+            "A,Plant,W,R,P,1.0,mg/L,1.0,m3/s,60,0.5,1.0,",
+            "   # interleaved note",
+            "B,Plant,W,R,P,x,mg/L,1.0,m3/s,60,0.5,1.0,",
+        ]
+        .join("\n");
+        let mut reader = CpvmNodeReader::from_reader(contents.as_bytes()).unwrap();
+        assert_eq!(
+            reader.next().unwrap().unwrap().node_id,
+            NodeId("A".to_string())
+        );
+        assert!(reader.next().unwrap().is_err());
+        assert_eq!(reader.line_number(), 7);
+        assert!(reader.next().is_none());
+
+        let custom = contents.replace('#', "//");
+        let opts = LoaderOptions {
+            comment_prefix: Some("//".to_string()),
+            ..LoaderOptions::default()
+        };
+        let reader = CpvmNodeReader::with_options(custom.as_bytes(), opts).unwrap();
+        assert_eq!(reader.count(), 2);
+
+        let opts = LoaderOptions {
+            comment_prefix: None,
+            ..LoaderOptions::default()
+        };
+        assert!(CpvmNodeReader::with_options(contents.as_bytes(), opts).is_err());
+    }
+
+    #[test]
+    fn test_from_str_case_insensitive() {
+        assert_eq!(AssetType::from_str("reservoir"), AssetType::Reservoir);
+        assert_eq!(AssetType::from_str(" RIVERREACH "), AssetType::RiverReach);
+        assert_eq!(
+            AssetType::from_str("watershedCluster"),
+            AssetType::WatershedCluster
+        );
+        assert_eq!(
+            AssetType::from_str(" WetLand "),
+            AssetType::Other("WetLand".to_string())
+        );
+
+        assert_eq!(
+            ConcentrationUnit::from_str("MG/L"),
+            ConcentrationUnit::MgPerL
+        );
+        assert_eq!(
+            ConcentrationUnit::from_str("ng/l"),
+            ConcentrationUnit::NgPerL
+        );
+        assert_eq!(
+            ConcentrationUnit::from_str("mpn/100ML"),
+            ConcentrationUnit::MpnPer100mL
+        );
+        assert_eq!(
+            ConcentrationUnit::from_str("PPB"),
+            ConcentrationUnit::Other("PPB".to_string())
+        );
+
+        assert_eq!(FlowUnit::from_str("M3/S"), FlowUnit::M3PerS);
+        assert_eq!(FlowUnit::from_str("l/s"), FlowUnit::LPerS);
+        assert_eq!(FlowUnit::from_str("mgd"), FlowUnit::MGD);
+        assert_eq!(
+            FlowUnit::from_str("mL/s"),
+            FlowUnit::Other("mL/s".to_string())
+        );
+    }
+
+    #[test]
+    fn test_canonical_str_round_trip() {
+        let assets = [
+            AssetType::Reservoir,
+            AssetType::Plant,
+            AssetType::RiverReach,
+            AssetType::Basin,
+            AssetType::WatershedCluster,
+            AssetType::Other("Wetland".to_string()),
+        ];
+        for a in assets {
+            assert_eq!(AssetType::from_str(a.as_canonical_str()), a);
+        }
+
+        let concentrations = [
+            ConcentrationUnit::NgPerL,
+            ConcentrationUnit::MgPerL,
+            ConcentrationUnit::MpnPer100mL,
+            ConcentrationUnit::Other("ppb".to_string()),
+        ];
+        for c in concentrations {
+            assert_eq!(ConcentrationUnit::from_str(c.as_canonical_str()), c);
+        }
+
+        let flows = [
+            FlowUnit::M3PerS,
+            FlowUnit::LPerS,
+            FlowUnit::MGD,
+            FlowUnit::Other("cfs".to_string()),
+        ];
+        for q in flows {
+            assert_eq!(FlowUnit::from_str(q.as_canonical_str()), q);
+        }
+
+        assert_eq!(ConcentrationUnit::NgPerL.as_canonical_str(), "ng/L");
+        assert_eq!(AssetType::Reservoir.as_canonical_str(), "Reservoir");
+    }
+
+    #[test]
+    fn test_cout_for_target_karma() {
+        let cfg = bind_cpvm_config(test_meta(1.0, FlowUnit::M3PerS), 5.0, 10.0, 100.0);
+        let max = evaluate_ecoimpact_for_node(&cfg, 0.0).karma_gain;
+
+        for target in [0.0, 1.0e5, 0.5 * max, max] {
+            let cout = cout_for_target_karma(&cfg, target).unwrap();
+            let karma = evaluate_ecoimpact_for_node(&cfg, cout).karma_gain;
+            assert!(
+                (karma - target).abs() <= 1e-6 * max,
+                "{} vs {}",
+                karma,
+                target
+            );
+        }
+        assert_eq!(cout_for_target_karma(&cfg, 1.01 * max), None);
+        assert_eq!(cout_for_target_karma(&cfg, -1.0), None);
+
+        let unreachable = |meta: CpvmNodeMeta| {
+            cout_for_target_karma(&bind_cpvm_config(meta, 5.0, 1.0, 1.0), 1.0).is_none()
+        };
+        let mut idle = test_meta(0.0, FlowUnit::M3PerS);
+        assert!(unreachable(idle.clone()));
+        idle.q_avg = 1.0;
+        idle.ecoimpactscore = 0.0;
+        assert!(unreachable(idle.clone()));
+        idle.ecoimpactscore = 0.5;
+        idle.horizon_s = 0.0;
+        assert!(unreachable(idle));
+    }
+
+    #[test]
+    fn test_weighted_total_karma() {
+        let id = |s: &str| NodeId(s.to_string());
+        let results = vec![
+            (id("A"), test_result(1.0, 0.5, 100.0)),
+            (id("B"), test_result(1.0, 0.5, 200.0)),
+            (id("C"), test_result(1.0, 0.5, 300.0)),
+            (id("D"), test_result(1.0, 0.5, 400.0)),
+        ];
+        let mut weights = HashMap::new();
+        weights.insert(id("A"), 2.0);
+        weights.insert(id("C"), 0.5);
+        weights.insert(id("UNUSED"), 10.0);
+
+        let w = weighted_total_karma(&results, &weights);
+        // 2*100 + 200 + 0.5*300 + 400
+        assert_eq!(w.total, 950.0);
+        assert_eq!(w.weight_coverage, 0.5);
+
+        let empty = weighted_total_karma(&[], &weights);
+        assert_eq!(empty.total, 0.0);
+        assert_eq!(empty.weight_coverage, 0.0);
+    }
+
+    #[test]
+    fn test_karma_fallback() {
+        let mut reach = test_meta(1.0, FlowUnit::M3PerS);
+        reach.asset_type = AssetType::RiverReach;
+        reach.karma_per_unit = 0.0;
+        reach.with_karma_fallback(&default_karma_per_unit);
+        assert_eq!(
+            reach.karma_per_unit,
+            default_karma_per_unit(&AssetType::RiverReach)
+        );
+
+        let mut valid = test_meta(1.0, FlowUnit::M3PerS);
+        valid.with_karma_fallback(&default_karma_per_unit);
+        assert_eq!(valid.karma_per_unit, 1.0e3);
+
+        let mut broken = test_meta(1.0, FlowUnit::M3PerS);
+        broken.asset_type = AssetType::Other("Canal".to_string());
+        broken.karma_per_unit = f64::NAN;
+        broken.with_karma_fallback(&|asset: &AssetType| match asset {
+            AssetType::Other(_) => 42.0,
+            other => default_karma_per_unit(other),
+        });
+        assert_eq!(broken.karma_per_unit, 42.0);
+        assert_eq!(
+            default_karma_per_unit(&AssetType::Other("x".to_string())),
+            1.0
+        );
+    }
+
+    #[test]
+    fn test_mass_unit_resolution() {
+        let mg = bind_cpvm_config(test_meta(1.0, FlowUnit::M3PerS), 5.0, 10.0, 100.0);
+        assert_eq!(evaluate_ecoimpact_for_node(&mg, 3.0).mass_unit, MassUnit::G);
+
+        let mut ng = test_meta(1.0, FlowUnit::MGD);
+        ng.cin_unit = ConcentrationUnit::NgPerL;
+        let ng = bind_cpvm_config(ng, 5.0, 10.0, 100.0);
+        assert_eq!(
+            evaluate_ecoimpact_for_node(&ng, 3.0).mass_unit,
+            MassUnit::Ug
+        );
+
+        let mut mpn = test_meta(1.0, FlowUnit::M3PerS);
+        mpn.cin_unit = ConcentrationUnit::MpnPer100mL;
+        let mpn = bind_cpvm_config(mpn, 5.0, 10.0, 100.0);
+        assert_eq!(
+            evaluate_ecoimpact_for_node(&mpn, 3.0).mass_unit,
+            MassUnit::Unknown
+        );
+
+        let cfs = bind_cpvm_config(
+            test_meta(1.0, FlowUnit::Other("cfs".to_string())),
+            5.0,
+            10.0,
+            100.0,
+        );
+        assert_eq!(
+            evaluate_ecoimpact_for_node(&cfs, 3.0).mass_unit,
+            MassUnit::Unknown
+        );
+
+        assert_eq!(MassUnit::G.to_kg_factor(), Some(1.0e-3));
+        assert_eq!(MassUnit::Unknown.to_kg_factor(), None);
+    }
+
+    #[test]
+    fn test_try_compute_mass_avoided_rejects_non_finite() {
+        let names = ["cin", "cout", "q_m3_per_s", "horizon_s"];
+        for bad in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+            for (idx, name) in names.iter().enumerate() {
+                let mut args = [10.0, 5.0, 2.0, 100.0];
+                args[idx] = bad;
+                let err = try_compute_mass_avoided(args[0], args[1], args[2], args[3])
+                    .unwrap_err()
+                    .to_string();
+                assert!(err.contains(name), "{}", err);
+                assert_eq!(
+                    compute_mass_avoided(args[0], args[1], args[2], args[3]),
+                    0.0
+                );
+            }
+        }
+        assert_eq!(
+            try_compute_mass_avoided(10.0, 5.0, 2.0, 100.0).unwrap(),
+            1000.0
+        );
+    }
+
+    #[test]
+    fn test_karma_sensitivity_matches_finite_difference() {
+        let cfg = bind_cpvm_config(test_meta(2.0, FlowUnit::LPerS), 5.0, 10.0, 100.0);
+        let h = 1e-3;
+        let cout = 4.0;
+        let k = |c: f64| evaluate_ecoimpact_for_node(&cfg, c).karma_gain;
+        let fd = (k(cout + h) - k(cout - h)) / (2.0 * h);
+        let analytic = karma_sensitivity_at(&cfg, cout);
+        assert!(
+            (fd - analytic).abs() <= 1e-6 * analytic.abs(),
+            "{} vs {}",
+            fd,
+            analytic
+        );
+        assert!((analytic - -0.8 * 2.0e-3 * 3600.0 * 1e3).abs() < 1e-9);
+
+        // Saturated: cout above cin_baseline (10.0) clamps the delta.
+        let fd_sat = (k(12.0 + h) - k(12.0 - h)) / (2.0 * h);
+        assert_eq!(fd_sat, 0.0);
+        assert_eq!(karma_sensitivity_at(&cfg, 12.0), 0.0);
+    }
+
+    #[test]
+    fn test_parse_f64_loose_formats() {
+        assert_eq!(parse_f64_loose("1e3").unwrap(), 1000.0);
+        assert_eq!(parse_f64_loose("1,234.5").unwrap(), 1234.5);
+        assert_eq!(parse_f64_loose("1_000").unwrap(), 1000.0);
+        assert_eq!(parse_f64_loose_with("1.234,5", ',').unwrap(), 1234.5);
+        assert_eq!(parse_f64_loose_with("2,5e-3", ',').unwrap(), 2.5e-3);
+        assert!(parse_f64_loose("1.2.3").is_err());
+        assert_eq!(parse_f64_loose("-1,234,567.5").unwrap(), -1_234_567.5);
+        assert_eq!(parse_f64_loose_with("1.234.567", ',').unwrap(), 1_234_567.0);
+        assert!(parse_f64_loose("").is_err());
+    }
+
+    #[test]
+    fn test_loader_decimal_separator_option() {
+        let contents = format!(
+            "{}\nN1,Plant,wb,rg,p,\"1.234,5\",mg/L,\"2,5\",m3/s,3600,\"0,5\",1e3,\n",
+            SHARD_HEADER
+        );
+        let opts = LoaderOptions {
+            decimal_separator: ',',
+            ..LoaderOptions::default()
+        };
+        let nodes: Vec<_> = CpvmNodeReader::with_options(contents.as_bytes(), opts)
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(nodes[0].cin_baseline, 1234.5);
+        assert_eq!(nodes[0].q_avg, 2.5);
+        assert_eq!(nodes[0].ecoimpactscore, 0.5);
+        assert_eq!(nodes[0].karma_per_unit, 1000.0);
+
+        let err = CpvmNodeReader::from_reader(
+            format!(
+                "{}\nN1,Plant,wb,rg,p,abc,mg/L,1,m3/s,3600,0.5,1,\n",
+                SHARD_HEADER
+            )
+            .as_bytes(),
+        )
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap_err();
+        assert!(
+            err.to_string().contains("cin_baseline parse error"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_strict_mode_rejects_over_wide_rows() {
+        let contents = format!(
+            "{}\nN1,Plant,wb,rg,p,10,mg/L,1,m3/s,3600,0.5,1,note,extra1,extra2\n",
+            SHARD_HEADER
+        );
+        let lenient: Vec<_> = CpvmNodeReader::from_reader(contents.as_bytes())
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(lenient[0].notes, "note");
+
+        let opts = LoaderOptions {
+            strict: true,
+            ..LoaderOptions::default()
+        };
+        let err = CpvmNodeReader::with_options(contents.as_bytes(), opts)
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("Line 2"), "{}", err);
+        assert!(err.contains("15 (expected 13)"), "{}", err);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_export_run_report_round_trip() {
+        let mut a = test_meta(1.0, FlowUnit::M3PerS);
+        a.region = Region::from("west");
+        let mut b = a.clone();
+        b.node_id = NodeId("OTHER".to_string());
+        b.region = Region::from("east");
+        let results = vec![
+            (a, test_result(10.0, 0.5, 100.0)),
+            (b, test_result(5.0, 0.9, 40.0)),
+        ];
+
+        let mut buf = Vec::new();
+        export_run_report(&mut buf, &results).unwrap();
+        let text = String::from_utf8(buf).unwrap();
+        assert!(text.contains("\n  \"by_region\""), "{}", text);
+
+        let v: serde_json::Value = serde_json::from_str(&text).unwrap();
+        assert_eq!(v["generated_nodes"], 2);
+        assert_eq!(v["total_karma"].as_f64().unwrap(), 140.0);
+        assert_eq!(v["total_mass_avoided"].as_f64().unwrap(), 15.0);
+        assert_eq!(
+            v["by_region"]["east"]["total_karma_gain"].as_f64().unwrap(),
+            40.0
+        );
+        assert_eq!(v["nodes"][1]["node_id"], "OTHER");
+        assert_eq!(v["nodes"][0]["mass_unit"], "g");
+    }
+
+    #[test]
+    fn test_ecoimpactscore_histogram() {
+        let nodes: Vec<CpvmNodeMeta> = [0.0, 0.1, 0.3, 0.55, 0.99, 1.0, 1.7, -0.2]
+            .iter()
+            .map(|&score| {
+                let mut m = test_meta(1.0, FlowUnit::M3PerS);
+                m.ecoimpactscore = score;
+                m
+            })
+            .collect();
+        let hist = ecoimpactscore_histogram(&nodes, 4);
+        let counts: Vec<usize> = hist.iter().map(|&(_, _, c)| c).collect();
+        assert_eq!(counts, vec![3, 1, 1, 3]);
+        assert_eq!((hist[0].0, hist[0].1), (0.0, 0.25));
+        assert_eq!(hist[3].1, 1.0);
+
+        let empty = ecoimpactscore_histogram(&[], 3);
+        assert_eq!(empty.len(), 3);
+        assert!(empty.iter().all(|&(_, _, c)| c == 0));
+
+        assert!(try_ecoimpactscore_histogram(&nodes, 0).is_err());
+    }
+
+    #[test]
+    fn test_find_threshold_violations() {
+        let mk = |id: &str, safe: f64| {
+            let mut meta = test_meta(1.0, FlowUnit::M3PerS);
+            meta.node_id = NodeId(id.to_string());
+            let mut cfg = bind_cpvm_config(meta, 5.0, 10.0, 100.0);
+            cfg.safety.safe_threshold = safe;
+            cfg
+        };
+        // cin_baseline is 10.0 in test_meta.
+        let configs = vec![
+            mk("OK", 12.0),
+            mk("BAD", 4.0),
+            mk("EDGE", 10.0),
+            mk("WORSE", 1.0),
+        ];
+        let ids: Vec<&str> = find_threshold_violations(&configs)
+            .iter()
+            .map(|c| c.meta.node_id.0.as_str())
+            .collect();
+        assert_eq!(ids, vec!["BAD", "WORSE"]);
+        assert_eq!(violation_margin(&configs[0]), -2.0);
+        assert_eq!(violation_margin(&configs[3]), 9.0);
+    }
+
+    #[test]
+    fn test_signed_mass_goes_negative() {
+        assert_eq!(compute_mass_avoided_signed(5.0, 8.0, 2.0, 10.0), -60.0);
+        assert_eq!(compute_mass_avoided_signed(8.0, 5.0, 2.0, 10.0), 60.0);
+        assert_eq!(compute_mass_avoided(5.0, 8.0, 2.0, 10.0), 0.0);
+
+        let cfg = bind_cpvm_config(test_meta(1.0, FlowUnit::M3PerS), 5.0, 10.0, 100.0);
+        assert_eq!(evaluate_ecoimpact_for_node(&cfg, 12.0).mass_avoided, 0.0);
+        let opts = EvalOptions {
+            signed_mass: true,
+            ..EvalOptions::default()
+        };
+        let signed = evaluate_ecoimpact_for_node_with_options(&cfg, 12.0, &opts);
+        assert_eq!(signed.mass_avoided, -2.0 * 3600.0);
+        assert!(signed.karma_gain < 0.0);
+    }
+
+    #[test]
+    fn test_merge_node_sets_policies() {
+        let node = |id: &str, notes: &str| {
+            let mut m = test_meta(1.0, FlowUnit::M3PerS);
+            m.node_id = NodeId(id.to_string());
+            m.notes = notes.to_string();
+            m
+        };
+        let sets = || {
+            vec![
+                vec![node("A", "a1"), node("B", "b1")],
+                vec![node("C", "c2"), node("A", "a2")],
+            ]
+        };
+        let summary = |nodes: &[CpvmNodeMeta]| -> Vec<String> {
+            nodes
+                .iter()
+                .map(|n| format!("{}:{}", n.node_id, n.notes))
+                .collect()
+        };
+
+        let first = merge_node_sets(sets(), ConflictPolicy::KeepFirst).unwrap();
+        assert_eq!(summary(&first), vec!["A:a1", "B:b1", "C:c2"]);
+
+        let last = merge_node_sets(sets(), ConflictPolicy::KeepLast).unwrap();
+        assert_eq!(summary(&last), vec!["A:a2", "B:b1", "C:c2"]);
+
+        let err = merge_node_sets(sets(), ConflictPolicy::Error).unwrap_err();
+        assert!(err.to_string().contains("Duplicate node_id: A"), "{}", err);
+    }
+
+    #[test]
+    fn test_region_waterbody_newtypes() {
+        let contents = format!(
+            "{}\nN1,Plant,Salt River,Maricopa,p,10,mg/L,1,m3/s,3600,0.5,1,\n",
+            SHARD_HEADER
+        );
+        let node = CpvmNodeReader::from_reader(contents.as_bytes())
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(node.waterbody, Waterbody::from("Salt River"));
+        assert_eq!(node.region, Region::from("Maricopa"));
+        assert_eq!(node.region.to_string(), "Maricopa");
+        assert_eq!(format!("{}", node.waterbody), "Salt River");
+    }
+
+    #[test]
+    fn test_node_evaluator_matches_full_evaluation() {
+        let cfg = bind_cpvm_config(test_meta(3.0, FlowUnit::LPerS), 5.0, 10.0, 100.0);
+        let eval = NodeEvaluator::from(&cfg);
+        for cout in [0.0, 2.5, 7.0, 10.0, 14.0] {
+            let full = evaluate_ecoimpact_for_node(&cfg, cout);
+            assert!((eval.karma_for(cout) - full.karma_gain).abs() <= 1e-9 * full.karma_gain.abs());
+            assert!((eval.mass_for(cout) - full.mass_avoided).abs() < 1e-12);
+        }
+
+        let unknown = bind_cpvm_config(
+            test_meta(3.0, FlowUnit::Other("cfs".to_string())),
+            5.0,
+            10.0,
+            100.0,
+        );
+        assert_eq!(NodeEvaluator::from(&unknown).karma_for(2.0), 0.0);
+    }
+
+    #[test]
+    fn test_split_csv_line_unterminated_quote() {
+        assert_eq!(split_csv_line("a,\"b,c"), vec!["a", "b,c"]);
+        assert_eq!(split_csv_line("\""), vec![""]);
+        assert_eq!(split_csv_line("a,\"\"\""), vec!["a", "\""]);
+    }
+
+    #[test]
+    fn test_split_csv_line_never_panics() {
+        // Small xorshift generator biased towards quotes and delimiters.
+        let alphabet = [
+            '"', '"', ',', ',', ';', ' ', 'a', 'Z', '\t', '\u{FEFF}', 'é', '\u{FFFD}',
+        ];
+        let mut state: u64 = 0x9E37_79B9_7F4A_7C15;
+        let mut next = || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        for _ in 0..2000 {
+            let len = (next() % 24) as usize;
+            let line: String = (0..len)
+                .map(|_| alphabet[(next() % alphabet.len() as u64) as usize])
+                .collect();
+            let fields = split_csv_line(&line);
+            if !line.is_empty() {
+                assert!(!fields.is_empty(), "{:?}", line);
+            }
+            let _ = split_line_with_delimiter(&line, ';');
+        }
+
+        // Malformed UTF-8 reaches the splitter only after lossy decoding.
+        let lossy = String::from_utf8_lossy(b"a,\xff\xfe,\"b");
+        assert_eq!(split_csv_line(&lossy).len(), 3);
+    }
+
+    #[test]
+    fn test_evaluate_shard_with_cout_map() {
+        let contents = format!(
+            "{}\nB,Plant,wb,rg,p,10,mg/L,1,m3/s,100,1.0,1,\n\
+             A,Plant,wb,rg,p,8,mg/L,2,m3/s,100,0.5,1,\n",
+            SHARD_HEADER
+        );
+        let path = write_temp_shard("evaluate_shard.csv", &contents);
+        let mut couts = HashMap::new();
+        couts.insert(NodeId("B".to_string()), 4.0);
+        let defaults = SafetyDefaults {
+            cref: 5.0,
+            lambda_clf: 10.0,
+            mu_cbf: 100.0,
+        };
+        let results = evaluate_shard(path.to_str().unwrap(), &couts, defaults).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        let ids: Vec<&str> = results.iter().map(|(id, _)| id.0.as_str()).collect();
+        assert_eq!(ids, vec!["B", "A"]);
+        assert_eq!(results[0].1.mass_avoided, 600.0);
+        assert_eq!(results[1].1.mass_avoided, 0.0);
+        assert!(evaluate_shard("/nonexistent/shard.csv", &couts, defaults).is_err());
+    }
+
+    #[test]
+    fn test_notes_read_as_single_field_with_header() {
+        let contents = format!(
+            "{}\nN1,Plant,wb,rg,p,10,mg/L,1,m3/s,3600,0.5,1,\"pump \"\"B\"\", east, bank\"\n",
+            SHARD_HEADER
+        );
+        let node = CpvmNodeReader::from_reader(contents.as_bytes())
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(node.notes, "pump \"B\", east, bank");
+
+        let mut buf = Vec::new();
+        write_cpvm_nodes_to_csv(&mut buf, std::slice::from_ref(&node)).unwrap();
+        let reloaded = load_cpvm_nodes_from_reader(buf.as_slice()).unwrap();
+        assert_eq!(reloaded[0].notes, node.notes);
+
+        // Headerless legacy shards still re-join unquoted overflow.
+        let opts = LoaderOptions {
+            has_header: false,
+            ..LoaderOptions::default()
+        };
+        let legacy = "N1,Plant,wb,rg,p,10,mg/L,1,m3/s,3600,0.5,1,east, bank\n";
+        let node = CpvmNodeReader::with_options(legacy.as_bytes(), opts)
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(node.notes, "east,bank");
+    }
+
+    #[test]
+    fn test_weighted_ecoimpact_curve() {
+        let mut meta = test_meta(1.0, FlowUnit::M3PerS);
+        meta.asset_type = AssetType::RiverReach;
+        meta.ecoimpactscore = 0.5;
+        assert!((effective_ecoimpactscore(&meta, &river_reach_boost_curve) - 0.6).abs() < 1e-12);
+
+        // 0.9 * 1.2 = 1.08 clamps to 1.0.
+        meta.ecoimpactscore = 0.9;
+        assert_eq!(
+            effective_ecoimpactscore(&meta, &river_reach_boost_curve),
+            1.0
+        );
+
+        let cfg = bind_cpvm_config(meta, 5.0, 10.0, 100.0);
+        let flat = evaluate_ecoimpact_for_node_weighted(&cfg, 4.0, None);
+        assert_eq!(flat.ecoimpactscore, 0.9);
+        assert_eq!(
+            flat.karma_gain,
+            evaluate_ecoimpact_for_node(&cfg, 4.0).karma_gain
+        );
+        let boosted =
+            evaluate_ecoimpact_for_node_weighted(&cfg, 4.0, Some(&river_reach_boost_curve));
+        assert_eq!(boosted.ecoimpactscore, 1.0);
+        assert_eq!(
+            boosted.karma_gain,
+            boosted.mass_avoided * cfg.meta.karma_per_unit
+        );
+    }
+
+    #[test]
+    fn test_check_mass_feasibility() {
+        let feasible = test_meta(1.0, FlowUnit::M3PerS);
+        let cfg = bind_cpvm_config(feasible.clone(), 5.0, 10.0, 100.0);
+        let full_removal = evaluate_ecoimpact_for_node(&cfg, 0.0);
+
+        let mut infeasible = test_meta(1.0, FlowUnit::M3PerS);
+        infeasible.node_id = NodeId("BAD".to_string());
+        // 10 mg/L * 1 m3/s * 3600 s = 36000 is the ceiling.
+        let results = vec![
+            (feasible, full_removal),
+            (infeasible, test_result(36_001.0, 0.8, 1.0)),
+        ];
+        assert_eq!(
+            check_mass_feasibility(&results),
+            vec![NodeId("BAD".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_parse_duration_to_seconds() {
+        assert_eq!(parse_duration_to_seconds("24h").unwrap(), 86_400.0);
+        assert_eq!(parse_duration_to_seconds("1y").unwrap(), 31_536_000.0);
+        assert_eq!(parse_duration_to_seconds("3600").unwrap(), 3600.0);
+        assert_eq!(parse_duration_to_seconds("30d").unwrap(), 2_592_000.0);
+        assert_eq!(parse_duration_to_seconds("1.5m").unwrap(), 90.0);
+        assert_eq!(parse_duration_to_seconds("45s").unwrap(), 45.0);
+        assert!(parse_duration_to_seconds("h").is_err());
+        assert!(parse_duration_to_seconds("3w").is_err());
+
+        let contents = format!(
+            "{}\nN1,Plant,wb,rg,p,10,mg/L,1,m3/s,24h,0.5,1,\n",
+            SHARD_HEADER
+        );
+        let node = CpvmNodeReader::from_reader(contents.as_bytes())
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(node.horizon_s, 86_400.0);
+    }
+
+    #[test]
+    fn test_field_parse_errors_carry_line_number() {
+        let good = [
+            "N1", "Plant", "wb", "rg", "p", "10", "mg/L", "1", "m3/s", "3600", "0.5", "1", "",
+        ];
+        let numeric = [
+            (5, "cin_baseline"),
+            (7, "q_avg"),
+            (9, "horizon_s"),
+            (10, "ecoimpactscore"),
+            (11, "karma_per_unit"),
+        ];
+        for (idx, name) in numeric {
+            let mut row = good;
+            row[idx] = "oops";
+            // Blank line and comment before the bad row: it is physical line 4.
+            let contents = format!("{}\n\n# comment\n{}\n", SHARD_HEADER, row.join(","));
+            let err = CpvmNodeReader::from_reader(contents.as_bytes())
+                .unwrap()
+                .next()
+                .unwrap()
+                .unwrap_err()
+                .to_string();
+            let expected = format!("Line 4: {} parse error", name);
+            assert!(err.contains(&expected), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_karma_ledger_decay() {
+        let a = NodeId("A".to_string());
+        let b = NodeId("B".to_string());
+        let mut ledger = KarmaLedger::new();
+        ledger.credit(&a, 100.0, 0.0);
+        ledger.credit(&b, 80.0, 1_000.0);
+
+        ledger.apply_decay(1_000.0, 1_000.0);
+        assert!((ledger.balance(&a) - 50.0).abs() < 1e-9);
+        assert!((ledger.balance(&b) - 80.0).abs() < 1e-9);
+        assert!((ledger.total_balance() - 130.0).abs() < 1e-9);
+
+        // Recomputed from the log, not compounded.
+        ledger.apply_decay(2_000.0, 1_000.0);
+        assert!((ledger.balance(&a) - 25.0).abs() < 1e-9);
+        assert!((ledger.balance(&b) - 40.0).abs() < 1e-9);
+        assert_eq!(ledger.transactions()[0].gain, 100.0);
+
+        ledger.apply_decay(5_000.0, 0.0);
+        assert!((ledger.balance(&a) - 25.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_threshold_policies() {
+        let meta = test_meta(1.0, FlowUnit::M3PerS);
+        let default = derive_safety_config(&meta, 5.0, 10.0, 100.0);
+        let explicit = derive_safety_config_with_policy(&meta, 5.0, 10.0, 100.0, &MinBaselineCref);
+        assert_eq!(default.safe_threshold, 5.0);
+        assert_eq!(explicit.safe_threshold, default.safe_threshold);
+
+        let frac = derive_safety_config_with_policy(&meta, 5.0, 10.0, 100.0, &FixedFraction(0.3));
+        assert!((frac.safe_threshold - 3.0).abs() < 1e-12);
+        assert_eq!(frac.cref, 5.0);
+
+        struct Constant;
+        impl ThresholdPolicy for Constant {
+            fn threshold(&self, _meta: &CpvmNodeMeta, _cref: f64) -> f64 {
+                7.5
+            }
+        }
+        let custom = derive_safety_config_with_policy(&meta, 5.0, 10.0, 100.0, &Constant);
+        assert_eq!(custom.safe_threshold, 7.5);
+    }
 
     #[test]
-    fn test_split_csv_line_basic() {
-        let line = "A,B,C";
-        let f = split_csv_line(line);
-        assert_eq!(f, vec!["A", "B", "C"]);
+    fn test_check_unit_consistency() {
+        let node = |waterbody: &str, unit: ConcentrationUnit| {
+            let mut m = test_meta(1.0, FlowUnit::M3PerS);
+            m.waterbody = Waterbody::from(waterbody);
+            m.cin_unit = unit;
+            m
+        };
+        let nodes = vec![
+            node("Salt River", ConcentrationUnit::MgPerL),
+            node("Verde", ConcentrationUnit::NgPerL),
+            node("Salt River", ConcentrationUnit::NgPerL),
+            node("Verde", ConcentrationUnit::NgPerL),
+            node("Salt River", ConcentrationUnit::MgPerL),
+        ];
+        let flagged = check_unit_consistency(&nodes);
+        assert_eq!(
+            flagged,
+            vec![(
+                Waterbody::from("Salt River"),
+                vec![ConcentrationUnit::MgPerL, ConcentrationUnit::NgPerL]
+            )]
+        );
     }
 
     #[test]
-    fn test_split_csv_line_quotes() {
-        let line = "A,\"B, with comma\",C";
-        let f = split_csv_line(line);
-        assert_eq!(f, vec!["A", "B, with comma", "C"]);
+    fn test_trim_fields_option() {
+        let contents = format!(
+            "{}\n\" A \",Plant,wb,rg,p,10,mg/L,1,m3/s,3600,0.5,1,\"  padded note \"\n",
+            SHARD_HEADER
+        );
+        let trimmed = CpvmNodeReader::from_reader(contents.as_bytes())
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(trimmed.node_id.0, "A");
+        assert_eq!(trimmed.notes, "padded note");
+
+        let opts = LoaderOptions {
+            trim_fields: false,
+            ..LoaderOptions::default()
+        };
+        let preserved = CpvmNodeReader::with_options(contents.as_bytes(), opts)
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(preserved.node_id.0, " A ");
+        assert_eq!(preserved.notes, "  padded note ");
+        assert_eq!(preserved.asset_type, AssetType::Plant);
     }
 
     #[test]
-    fn test_compute_mass_avoided() {
-        let m = compute_mass_avoided(10.0, 5.0, 2.0, 100.0);
-        assert!((m - 1000.0).abs() < 1e-6);
+    fn test_percentile_threshold() {
+        let nodes: Vec<CpvmNodeMeta> = [4.0, 1.0, 3.0, 2.0, 10.0]
+            .iter()
+            .map(|&c| {
+                let mut m = test_meta(1.0, FlowUnit::M3PerS);
+                m.cin_baseline = c;
+                m
+            })
+            .collect();
+        assert_eq!(percentile_threshold(&nodes, 50.0), Some(3.0));
+        assert_eq!(percentile_threshold(&nodes, 0.0), Some(1.0));
+        assert_eq!(percentile_threshold(&nodes, 100.0), Some(10.0));
+        // rank 0.25 * 4 = 1.0 -> 2.0; rank 0.9 * 4 = 3.6 -> 4 + 0.6 * 6.
+        assert_eq!(percentile_threshold(&nodes, 25.0), Some(2.0));
+        assert!((percentile_threshold(&nodes, 90.0).unwrap() - 7.6).abs() < 1e-12);
+        assert_eq!(percentile_threshold(&nodes[..4], 50.0), Some(2.5));
+        assert_eq!(percentile_threshold(&[], 50.0), None);
+
+        let policy = BasinPercentile::from_nodes(&nodes, 25.0).unwrap();
+        let cfg = derive_safety_config_with_policy(&nodes[4], 5.0, 10.0, 100.0, &policy);
+        assert_eq!(cfg.safe_threshold, 2.0);
     }
 
     #[test]
-    fn test_evaluate_ecoimpact_for_node() {
-        let meta = CpvmNodeMeta {
-            node_id: NodeId("TEST-NODE".to_string()),
-            asset_type: AssetType::Plant,
-            waterbody: "TestRiver".to_string(),
-            region: "TestRegion".to_string(),
-            cpvm_profile: "TEST_PROFILE".to_string(),
-            cin_baseline: 10.0,
-            cin_unit: ConcentrationUnit::MgPerL,
-            q_avg: 1.0,
-            q_unit: FlowUnit::M3PerS,
-            horizon_s: 3600.0,
-            ecoimpactscore: 0.8,
-            karma_per_unit: 1.0e3,
-            notes: "Test node".to_string(),
+    fn test_asset_type_aliases() {
+        let mut aliases = default_asset_aliases();
+        assert_eq!(
+            AssetType::from_str_with_aliases("WWTP", &aliases),
+            AssetType::Plant
+        );
+        assert_eq!(
+            AssetType::from_str_with_aliases(" treatment ", &aliases),
+            AssetType::Plant
+        );
+        assert_eq!(
+            AssetType::from_str_with_aliases("Dam", &aliases),
+            AssetType::Reservoir
+        );
+        assert_eq!(
+            AssetType::from_str_with_aliases("basin", &aliases),
+            AssetType::Basin
+        );
+        assert_eq!(
+            AssetType::from_str_with_aliases("Wetland", &aliases),
+            AssetType::Other("Wetland".to_string())
+        );
+        // Plain from_str is unaffected.
+        assert_eq!(
+            AssetType::from_str("WWTP"),
+            AssetType::Other("WWTP".to_string())
+        );
+
+        aliases.insert("canal".to_string(), AssetType::RiverReach);
+        assert_eq!(
+            AssetType::from_str_with_aliases("Canal", &aliases),
+            AssetType::RiverReach
+        );
+    }
+
+    #[test]
+    fn test_running_aggregate_matches_batch() {
+        let contents = format!(
+            "{}\nN1,Plant,wb,North,p,10,mg/L,1,m3/s,100,0.5,1,\n\
+             N2,Plant,wb,South,p,8,mg/L,2,m3/s,100,0.9,1,\n\
+             N3,Plant,wb,North,p,6,mg/L,3,m3/s,100,0.2,1,\n",
+            SHARD_HEADER
+        );
+        let mut running = RunningAggregate::new();
+        let mut collected = Vec::new();
+        for meta in CpvmNodeReader::from_reader(contents.as_bytes()).unwrap() {
+            let cfg = bind_cpvm_config(meta.unwrap(), 5.0, 10.0, 100.0);
+            let result = evaluate_ecoimpact_for_node(&cfg, 3.0);
+            running.add(&cfg.meta.region, &result);
+            collected.push((cfg.meta, result));
+        }
+
+        let batch = aggregate_by_region(&collected);
+        assert_eq!(running.by_region(), &batch);
+        assert_eq!(
+            running.region(&Region::from("North")),
+            batch.get(&Region::from("North"))
+        );
+        assert_eq!(running.total().node_count, 3);
+        let total_karma: f64 = collected.iter().map(|(_, r)| r.karma_gain).sum();
+        assert!((running.total().total_karma_gain - total_karma).abs() < 1e-9);
+        assert!(running.region(&Region::from("East")).is_none());
+    }
+
+    #[test]
+    fn test_max_karma_and_fraction() {
+        let cfg = bind_cpvm_config(test_meta(2.0, FlowUnit::M3PerS), 5.0, 10.0, 100.0);
+        // 0.8 * 10 mg/L * 2 m3/s * 3600 s * 1e3.
+        assert!((max_karma(&cfg) - 0.8 * 10.0 * 2.0 * 3600.0 * 1e3).abs() < 1e-6);
+        assert_eq!(karma_fraction_achieved(&cfg, 0.0), 1.0);
+        assert_eq!(karma_fraction_achieved(&cfg, 10.0), 0.0);
+        assert!((karma_fraction_achieved(&cfg, 2.5) - 0.75).abs() < 1e-12);
+
+        let mut zero = cfg.clone();
+        zero.meta.cin_baseline = 0.0;
+        assert_eq!(max_karma(&zero), 0.0);
+        assert_eq!(karma_fraction_achieved(&zero, 0.0), 0.0);
+    }
+
+    #[test]
+    fn test_min_fields_derived_from_header() {
+        // Optional `operator` column sits before `karma_per_unit` and `notes`.
+        let header = "node_id,asset_type,waterbody,region,cpvm_profile,cin_baseline,cin_unit,\
+q_avg,q_unit,horizon_s,ecoimpactscore,operator,karma_per_unit,notes";
+        let contents = format!(
+            "{}\nN1,Plant,wb,rg,p,10,mg/L,1,m3/s,3600,0.5,SRP,2.5,hello\n\
+             N2,Plant,wb,rg,p,10,mg/L,1,m3/s,3600,0.5,SRP,2.5\n\
+             N3,Plant,wb,rg,p,10,mg/L,1,m3/s,3600,0.5,SRP\n",
+            header
+        );
+        let rows: Vec<_> = CpvmNodeReader::from_reader(contents.as_bytes())
+            .unwrap()
+            .collect();
+        let n1 = rows[0].as_ref().unwrap();
+        assert_eq!((n1.karma_per_unit, n1.notes.as_str()), (2.5, "hello"));
+        assert_eq!(rows[1].as_ref().unwrap().notes, "");
+        // Twelve fields, but karma_per_unit (column 13) is missing.
+        let err = rows[2].as_ref().unwrap_err().to_string();
+        let expected = "Line 4 has insufficient fields: 12 (expected at least 13)";
+        assert!(err.contains(expected), "{}", err);
+    }
+
+    #[test]
+    fn test_diff_runs() {
+        let id = |s: &str| NodeId(s.to_string());
+        let before = vec![
+            (id("KEPT"), test_result(10.0, 0.5, 100.0)),
+            (id("REMOVED"), test_result(2.0, 0.5, 30.0)),
+        ];
+        let after = vec![
+            (id("ADDED"), test_result(4.0, 0.5, 500.0)),
+            (id("KEPT"), test_result(12.0, 0.5, 80.0)),
+        ];
+        let diffs = diff_runs(&before, &after);
+        let order: Vec<&str> = diffs.iter().map(|d| d.node.0.as_str()).collect();
+        assert_eq!(order, vec!["ADDED", "REMOVED", "KEPT"]);
+
+        assert_eq!(diffs[0].karma_before, 0.0);
+        assert_eq!(diffs[0].karma_delta, 500.0);
+        assert_eq!(diffs[1].karma_after, 0.0);
+        assert_eq!(diffs[1].karma_delta, -30.0);
+        assert_eq!(diffs[1].mass_delta, -2.0);
+        assert_eq!(diffs[2].karma_delta, -20.0);
+        assert_eq!(diffs[2].mass_delta, 2.0);
+    }
+
+    #[test]
+    fn test_asset_type_predicates() {
+        let cases = [
+            (AssetType::Reservoir, (true, false, false)),
+            (AssetType::Plant, (false, false, true)),
+            (AssetType::RiverReach, (false, true, false)),
+            (AssetType::Basin, (true, false, false)),
+            (AssetType::WatershedCluster, (true, false, false)),
+            (
+                AssetType::Other("Wetland".to_string()),
+                (false, false, false),
+            ),
+        ];
+        for (asset, expected) in cases {
+            let got = (
+                asset.is_storage(),
+                asset.is_flowing(),
+                asset.is_engineered(),
+            );
+            assert_eq!(got, expected, "{}", asset);
+        }
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn test_gzip_shards() {
+        use flate2::write::GzEncoder;
+        use flate2::Compression;
+
+        let contents = format!(
+            "{}\nN1,Plant,wb,rg,p,10,mg/L,1,m3/s,3600,0.5,1,zipped\n",
+            SHARD_HEADER
+        );
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(contents.as_bytes()).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let decoded = BufReader::new(flate2::read::GzDecoder::new(compressed.as_slice()));
+        let nodes = load_cpvm_nodes_from_reader(decoded).unwrap();
+        assert_eq!(nodes[0].notes, "zipped");
+
+        // Detected by extension, by magic bytes, and plain CSV still loads.
+        let by_ext = write_temp_shard("gzip_ext.csv.gz", "");
+        std::fs::write(&by_ext, &compressed).unwrap();
+        let by_magic = write_temp_shard("gzip_magic.csv", "");
+        std::fs::write(&by_magic, &compressed).unwrap();
+        let plain = write_temp_shard("gzip_plain.csv", &contents);
+        for path in [&by_ext, &by_magic, &plain] {
+            let nodes = load_cpvm_nodes_from_csv(path.to_str().unwrap()).unwrap();
+            std::fs::remove_file(path).ok();
+            assert_eq!(nodes.len(), 1);
+            assert_eq!(nodes[0].node_id.0, "N1");
+        }
+
+        // The other path-based loaders accept compressed shards too.
+        let by_ext = write_temp_shard("gzip_siblings.csv.gz", "");
+        std::fs::write(&by_ext, &compressed).unwrap();
+        let path = by_ext.to_str().unwrap();
+        assert_eq!(load_cpvm_nodes_detailed(path).unwrap().nodes.len(), 1);
+        assert_eq!(estimate_shard(path).unwrap().row_count, 1);
+        assert_eq!(inspect_shard_columns(path).unwrap()[0], "node_id");
+        let (nodes, errors) = load_cpvm_nodes_collect_errors(path);
+        assert_eq!((nodes.len(), errors.len()), (1, 0));
+        std::fs::remove_file(&by_ext).ok();
+
+        let setpoints = write_temp_shard("gzip_setpoints.csv.gz", "");
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(b"node_id,cout\nN1,4\n").unwrap();
+        std::fs::write(&setpoints, encoder.finish().unwrap()).unwrap();
+        let loaded = load_cout_setpoints(setpoints.to_str().unwrap()).unwrap();
+        std::fs::remove_file(&setpoints).ok();
+        assert_eq!(
+            loaded[&NodeId("N1".to_string())],
+            (4.0, ConcentrationUnit::MgPerL)
+        );
+    }
+
+    #[test]
+    fn test_aggregate_by_region_sorted() {
+        let entry = |region: &str, karma: f64| {
+            let mut m = test_meta(1.0, FlowUnit::M3PerS);
+            m.region = Region::from(region);
+            (m, test_result(1.0, 0.5, karma))
         };
+        let results = vec![
+            entry("Pinal", 5.0),
+            entry("Maricopa", 20.0),
+            entry("Yavapai", 20.0),
+            entry("Gila", 1.0),
+            entry("Pinal", 30.0),
+        ];
+        let names = |v: Vec<(Region, RegionSummary)>| -> Vec<String> {
+            v.into_iter().map(|(r, _)| r.0).collect()
+        };
+        let by_name = names(aggregate_by_region_sorted(&results));
+        assert_eq!(by_name, vec!["Gila", "Maricopa", "Pinal", "Yavapai"]);
+        let by_karma = names(aggregate_by_region_sorted_by_karma(&results));
+        assert_eq!(by_karma, vec!["Pinal", "Maricopa", "Yavapai", "Gila"]);
+        for _ in 0..5 {
+            assert_eq!(names(aggregate_by_region_sorted(&results)), by_name);
+            assert_eq!(
+                names(aggregate_by_region_sorted_by_karma(&results)),
+                by_karma
+            );
+        }
+    }
+
+    #[test]
+    fn test_error_kind_and_line() {
+        let contents = format!(
+            "{}\nN1,Plant,wb,rg,p,10,mg/L,1,m3/s,3600,0.5,1,\n# note\n\
+             N2,Plant,wb,rg,p,x,mg/L,1,m3/s,3600,0.5,1,\nN3,Plant\n",
+            SHARD_HEADER
+        );
+        let errors: Vec<CpvmLinkerError> = CpvmNodeReader::from_reader(contents.as_bytes())
+            .unwrap()
+            .filter_map(Result::err)
+            .collect();
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].kind(), CpvmErrorKind::Parse);
+        assert_eq!(errors[0].line(), Some(4));
+        assert_eq!(
+            errors[0].to_string(),
+            "Parse error: Line 4: cin_baseline parse error: \
+             invalid number \"x\": invalid float literal"
+        );
+        assert_eq!(errors[1].line(), Some(5));
+
+        let io = load_cpvm_nodes_from_csv("/nonexistent/shard.csv").unwrap_err();
+        assert_eq!(io.kind(), CpvmErrorKind::Io);
+        assert_eq!(io.line(), None);
+        assert_eq!(CpvmLinkerError::Parse("x".to_string()).line(), None);
+    }
 
+    #[test]
+    fn test_strict_score_option() {
+        let mut meta = test_meta(1.0, FlowUnit::M3PerS);
+        meta.ecoimpactscore = 1.5;
         let cfg = bind_cpvm_config(meta, 5.0, 10.0, 100.0);
-        let res = evaluate_ecoimpact_for_node(&cfg, 3.0);
 
-        assert!(res.mass_avoided > 0.0);
-        assert!(res.karma_gain > 0.0);
-        assert!(res.ecoimpactscore <= 1.0);
+        let lenient = evaluate_ecoimpact_for_node_opts(&cfg, 4.0, &EvalOptions::default()).unwrap();
+        assert_eq!(lenient.ecoimpactscore, 1.0);
+        assert_eq!(
+            lenient.karma_gain,
+            evaluate_ecoimpact_for_node(&cfg, 4.0).karma_gain
+        );
+
+        let strict = EvalOptions {
+            strict_score: true,
+            ..EvalOptions::default()
+        };
+        let err = evaluate_ecoimpact_for_node_opts(&cfg, 4.0, &strict).unwrap_err();
+        assert!(err.to_string().contains("ecoimpactscore = 1.5"), "{}", err);
+    }
+
+    #[test]
+    fn test_tags_parse_filter_and_round_trip() {
+        let header = format!("{},tags", SHARD_HEADER);
+        let contents = format!(
+            "{}\nN1,Plant,wb,rg,p,10,mg/L,1,m3/s,3600,0.5,1,,a|b|c\n\
+             N2,Plant,wb,rg,p,10,mg/L,1,m3/s,3600,0.5,1,, pilot | |pfas\n\
+             N3,Plant,wb,rg,p,10,mg/L,1,m3/s,3600,0.5,1,,\n",
+            header
+        );
+        let nodes = load_cpvm_nodes_from_reader(contents.as_bytes()).unwrap();
+        assert_eq!(nodes[0].tags, vec!["a", "b", "c"]);
+        assert_eq!(nodes[1].tags, vec!["pilot", "pfas"]);
+        assert!(nodes[2].tags.is_empty());
+
+        let pilots: Vec<&str> = filter_by_tag(&nodes, "pilot")
+            .iter()
+            .map(|n| n.node_id.0.as_str())
+            .collect();
+        assert_eq!(pilots, vec!["N2"]);
+        assert_eq!(filter_by_tag(&nodes, "b").len(), 1);
+
+        let mut buf = Vec::new();
+        write_cpvm_nodes_to_csv(&mut buf, &nodes).unwrap();
+        let reloaded = load_cpvm_nodes_from_reader(buf.as_slice()).unwrap();
+        assert_eq!(reloaded, nodes);
+
+        // Shards without a tags column load with no tags.
+        let plain = format!(
+            "{}\nN1,Plant,wb,rg,p,10,mg/L,1,m3/s,3600,0.5,1,\n",
+            SHARD_HEADER
+        );
+        assert!(load_cpvm_nodes_from_reader(plain.as_bytes()).unwrap()[0]
+            .tags
+            .is_empty());
+    }
+
+    #[test]
+    fn test_basin_blended_cout() {
+        let a = test_meta(3.0, FlowUnit::M3PerS);
+        let b = test_meta(1000.0, FlowUnit::LPerS);
+        // (2 * 3 + 6 * 1) / (3 + 1) = 3.
+        let blended = basin_blended_cout(&[(a.clone(), 2.0), (b, 6.0)]).unwrap();
+        assert!((blended - 3.0).abs() < 1e-12);
+
+        let mut ng = test_meta(1.0, FlowUnit::M3PerS);
+        ng.cin_unit = ConcentrationUnit::NgPerL;
+        assert_eq!(basin_blended_cout(&[(a.clone(), 2.0), (ng, 6.0)]), None);
+        assert_eq!(basin_blended_cout(&[]), None);
+        assert_eq!(
+            basin_blended_cout(&[(test_meta(0.0, FlowUnit::M3PerS), 2.0)]),
+            None
+        );
+    }
+
+    #[test]
+    fn test_load_cpvm_nodes_from_offset() {
+        let contents = format!(
+            "# exported\n{}\r\nA,Plant,wb,rg,p,1,mg/L,1,m3/s,3600,0.5,1,\n\
+             B,Plant,wb,rg,p,2,mg/L,1,m3/s,3600,0.5,1,\r\n\
+             C,Plant,wb,rg,p,3,mg/L,1,m3/s,3600,0.5,1,\n\
+             D,Plant,wb,rg,p,4,mg/L,1,m3/s,3600,0.5,1,",
+            SHARD_HEADER
+        );
+        let path = write_temp_shard("resume_offset.csv", &contents);
+        let path_str = path.to_str().unwrap();
+        let full = load_cpvm_nodes_from_csv(path_str).unwrap();
+
+        let mut reader = CpvmNodeReader::open(path_str).unwrap();
+        reader.next().unwrap().unwrap();
+        reader.next().unwrap().unwrap();
+        let checkpoint = reader.checkpoint();
+        assert_eq!(checkpoint.line_number, 4);
+
+        let (tail, end) = load_cpvm_nodes_from_checkpoint(path_str, checkpoint).unwrap();
+        assert_eq!(tail, full[2..].to_vec());
+        assert_eq!(end.byte_offset, contents.len() as u64);
+        assert_eq!(end.line_number, 6);
+
+        let (all, _) =
+            load_cpvm_nodes_from_checkpoint(path_str, ShardCheckpoint::default()).unwrap();
+        assert_eq!(all, full);
+        let (none, end_again) = load_cpvm_nodes_from_checkpoint(path_str, end).unwrap();
+        assert!(none.is_empty());
+        assert_eq!(end_again, end);
+
+        // The plain byte-offset entry point resumes the same way.
+        let (tail, end) = load_cpvm_nodes_from_offset(path_str, reader.byte_offset()).unwrap();
+        assert_eq!(tail, full[2..].to_vec());
+        assert_eq!(end, contents.len() as u64);
+        let (all, _) = load_cpvm_nodes_from_offset(path_str, 0).unwrap();
+        assert_eq!(all, full);
+        let (none, end_again) = load_cpvm_nodes_from_offset(path_str, end).unwrap();
+        std::fs::remove_file(&path).ok();
+        assert!(none.is_empty());
+        assert_eq!(end_again, end);
+    }
+
+    #[test]
+    fn test_load_cpvm_nodes_from_offset_reports_physical_lines() {
+        let contents = format!(
+            "# exported\n{}\nA,Plant,wb,rg,p,1,mg/L,1,m3/s,3600,0.5,1,\n\n\
+             B,Plant,wb,rg,p,bad,mg/L,1,m3/s,3600,0.5,1,\n",
+            SHARD_HEADER
+        );
+        let path = write_temp_shard("resume_offset_lines.csv", &contents);
+        let path_str = path.to_str().unwrap();
+        let mut reader = CpvmNodeReader::open(path_str).unwrap();
+        reader.next().unwrap().unwrap();
+        let checkpoint = reader.checkpoint();
+        let err = load_cpvm_nodes_from_checkpoint(path_str, checkpoint).unwrap_err();
+        std::fs::remove_file(&path).ok();
+        assert_eq!(err.line(), Some(5));
+        assert!(err.to_string().contains("Line 5"), "{}", err);
+    }
+
+    #[test]
+    fn test_evaluate_with_model() {
+        let cfg = bind_cpvm_config(test_meta(2.0, FlowUnit::MGD), 5.0, 10.0, 100.0);
+        for cout in [0.0, 3.0, 9.5, 12.0] {
+            let linear = evaluate_with_model(&cfg, cout, &LinearRemoval);
+            let current = evaluate_ecoimpact_for_node(&cfg, cout);
+            assert_eq!(linear.mass_avoided, current.mass_avoided);
+            assert_eq!(linear.karma_gain, current.karma_gain);
+            assert_eq!(linear.mass_unit, current.mass_unit);
+        }
+
+        struct HalfRemoval;
+        impl RemovalModel for HalfRemoval {
+            fn mass_avoided(&self, meta: &CpvmNodeMeta, cout: f64) -> f64 {
+                0.5 * LinearRemoval.mass_avoided(meta, cout)
+            }
+        }
+        let half = evaluate_with_model(&cfg, 3.0, &HalfRemoval);
+        let full = evaluate_with_model(&cfg, 3.0, &LinearRemoval);
+        assert!((half.karma_gain - 0.5 * full.karma_gain).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_render_prometheus() {
+        let mut meta = test_meta(1.0, FlowUnit::M3PerS);
+        meta.node_id = NodeId("PHX-1".to_string());
+        meta.region = Region::from("Valley \"East\"\\\nZone");
+        let results = vec![(meta, test_result(2.5, 0.5, 123.0))];
+        let text = render_prometheus(&results);
+
+        let labels = r#"{node="PHX-1",region="Valley \"East\"\\\nZone"}"#;
+        assert!(
+            text.contains(&format!("cpvm_karma_gain{} 123.0\n", labels)),
+            "{}",
+            text
+        );
+        assert!(
+            text.contains(&format!("cpvm_mass_avoided{} 2.5\n", labels)),
+            "{}",
+            text
+        );
+        assert!(text.contains("# TYPE cpvm_total_karma gauge\ncpvm_total_karma 123.0\n"));
+        // Every sample stays on a single line.
+        assert_eq!(text.lines().count(), 6);
+
+        assert_eq!(prometheus_value(f64::INFINITY), "+Inf");
+        assert_eq!(prometheus_value(f64::NAN), "NaN");
+    }
+
+    #[test]
+    fn test_load_cpvm_nodes_detailed() {
+        let cases = [
+            ("detailed_empty.csv", String::new(), false, 0),
+            (
+                "detailed_header.csv",
+                format!("{}\n", SHARD_HEADER),
+                true,
+                0,
+            ),
+            (
+                "detailed_rows.csv",
+                format!(
+                    "{}\nA,Plant,wb,rg,p,1,mg/L,1,m3/s,3600,0.5,1,\n\n\
+                     B,Plant,wb,rg,p,2,mg/L,1,m3/s,3600,0.5,1,\n",
+                    SHARD_HEADER
+                ),
+                true,
+                2,
+            ),
+        ];
+        for (name, contents, had_header, rows) in cases {
+            let path = write_temp_shard(name, &contents);
+            let outcome = load_cpvm_nodes_detailed(path.to_str().unwrap()).unwrap();
+            std::fs::remove_file(&path).ok();
+            assert_eq!(outcome.had_header, had_header, "{}", name);
+            assert_eq!(outcome.data_rows_seen, rows, "{}", name);
+            assert_eq!(outcome.nodes.len(), rows, "{}", name);
+            assert!(outcome.errors.is_empty(), "{}", name);
+        }
+
+        let contents = format!(
+            "{}\nA,Plant,wb,rg,p,1,mg/L,1,m3/s,3600,0.5,1,\n\
+             B,Plant,wb,rg,p,oops,mg/L,1,m3/s,3600,0.5,1,\n\
+             C,too,short\n",
+            SHARD_HEADER
+        );
+        let path = write_temp_shard("detailed_failed.csv", &contents);
+        let outcome = load_cpvm_nodes_detailed(path.to_str().unwrap()).unwrap();
+        std::fs::remove_file(&path).ok();
+        assert_eq!(outcome.data_rows_seen, 3);
+        assert_eq!(outcome.nodes.len(), 1);
+        let lines: Vec<usize> = outcome.errors.iter().map(|(line, _)| *line).collect();
+        assert_eq!(lines, vec![3, 4]);
+        assert!(outcome.errors[0].1.to_string().contains("cin_baseline"));
+        assert_eq!(outcome.errors[1].1.line(), Some(4));
+    }
+
+    #[test]
+    fn test_karma_efficiency_ranking() {
+        assert_eq!(karma_efficiency(&test_result(0.0, 0.5, 0.0)), None);
+        assert_eq!(karma_efficiency(&test_result(4.0, 0.5, 10.0)), Some(2.5));
+
+        let id = |s: &str| NodeId(s.to_string());
+        let results = vec![
+            (id("LOW"), test_result(10.0, 0.5, 10.0)),
+            (id("ZERO"), test_result(0.0, 0.5, 0.0)),
+            (id("HIGH"), test_result(1.0, 0.5, 50.0)),
+            (id("MID"), test_result(2.0, 0.5, 10.0)),
+        ];
+        let ranked = rank_by_efficiency(&results);
+        assert_eq!(
+            ranked,
+            vec![(id("HIGH"), 50.0), (id("MID"), 5.0), (id("LOW"), 1.0)]
+        );
+    }
+
+    #[test]
+    fn test_secondary_contaminant_columns() {
+        let header = format!("{},sec_name,sec_cin,sec_unit", SHARD_HEADER);
+        let contents = format!(
+            "{}\nA,Plant,wb,rg,p,10,mg/L,1,m3/s,100,1.0,1,,PFOA,40,ng/L\n\
+             B,Plant,wb,rg,p,10,mg/L,1,m3/s,100,1.0,1,,,,\n",
+            header
+        );
+        let nodes = load_cpvm_nodes_from_reader(contents.as_bytes()).unwrap();
+        let sec = nodes[0].secondary.as_ref().unwrap();
+        assert_eq!(sec.name, "PFOA");
+        assert_eq!(sec.cin_baseline, 40.0);
+        assert_eq!(sec.cin_unit, ConcentrationUnit::NgPerL);
+        assert!(nodes[1].secondary.is_none());
+
+        let plain = format!(
+            "{}\nA,Plant,wb,rg,p,10,mg/L,1,m3/s,100,1.0,1,\n",
+            SHARD_HEADER
+        );
+        assert!(load_cpvm_nodes_from_reader(plain.as_bytes()).unwrap()[0]
+            .secondary
+            .is_none());
+
+        let cfg = bind_cpvm_config(nodes[0].clone(), 5.0, 10.0, 100.0);
+        let (primary, secondary) = evaluate_ecoimpact_secondary(&cfg, 4.0, 10.0);
+        assert_eq!(primary.mass_avoided, 600.0);
+        let secondary = secondary.unwrap();
+        assert_eq!(secondary.mass_avoided, 3000.0);
+        assert_eq!(secondary.mass_unit, MassUnit::Ug);
+        let cfg_b = bind_cpvm_config(nodes[1].clone(), 5.0, 10.0, 100.0);
+        assert!(evaluate_ecoimpact_secondary(&cfg_b, 4.0, 10.0).1.is_none());
+
+        let mut buf = Vec::new();
+        write_cpvm_nodes_to_csv(&mut buf, &nodes).unwrap();
+        assert_eq!(load_cpvm_nodes_from_reader(buf.as_slice()).unwrap(), nodes);
+    }
+
+    #[test]
+    fn test_negative_karma_per_unit_warning() {
+        let mut meta = test_meta(1.0, FlowUnit::M3PerS);
+        meta.karma_per_unit = -5.0;
+        let err = validate_node_meta(&meta).unwrap_err().to_string();
+        assert!(err.contains("karma_per_unit = -5 must be >= 0"), "{}", err);
+
+        let contents = format!(
+            "{}\nGOOD,Plant,wb,rg,p,10,mg/L,1,m3/s,100,1.0,2,\n\
+             BAD,Plant,wb,rg,p,10,mg/L,1,m3/s,100,1.0,-3e5,\n",
+            SHARD_HEADER
+        );
+        let path = write_temp_shard("negative_karma.csv", &contents);
+        let (nodes, warnings) = load_cpvm_nodes_with_warnings(path.to_str().unwrap()).unwrap();
+        std::fs::remove_file(&path).ok();
+        assert_eq!(nodes[0].karma_per_unit, 2.0);
+        assert_eq!(nodes[1].karma_per_unit, 0.0);
+        assert_eq!(
+            warnings,
+            vec![Warning::NegativeKarmaPerUnit(NodeId("BAD".to_string()))]
+        );
+    }
+
+    #[test]
+    fn test_inspect_shard_columns() {
+        let contents = "# partner shard\n\nnode_id,\"sec_name\",\"notes, free text\",tags\n\
+                        A,cr,\"x\",t\n";
+        let path = write_temp_shard("inspect_columns.csv", contents);
+        let columns = inspect_shard_columns(path.to_str().unwrap()).unwrap();
+        std::fs::remove_file(&path).ok();
+        assert_eq!(
+            columns,
+            vec!["node_id", "sec_name", "notes, free text", "tags"]
+        );
+
+        let path = write_temp_shard("inspect_columns_empty.csv", "");
+        let columns = inspect_shard_columns(path.to_str().unwrap()).unwrap();
+        std::fs::remove_file(&path).ok();
+        assert!(columns.is_empty());
+    }
+
+    #[test]
+    fn test_normalize_to_horizon() {
+        let hourly = test_result(100.0, 0.5, 40.0);
+        assert_eq!(mass_avoided_rate(&hourly, 3600.0), 100.0 / 3600.0);
+        assert_eq!(karma_rate(&hourly, 0.0), 0.0);
+        let daily = normalize_to_horizon(&hourly, 3600.0, 86_400.0);
+        assert!((daily.mass_avoided - 2400.0).abs() < 1e-9);
+        assert!((daily.karma_gain - 960.0).abs() < 1e-9);
+        assert_eq!(daily.ecoimpactscore, 0.5);
+        assert_eq!(daily.mass_unit, hourly.mass_unit);
+    }
+
+    #[derive(Default)]
+    struct RecordingObserver {
+        events: std::cell::RefCell<Vec<String>>,
+    }
+
+    impl LoaderObserver for RecordingObserver {
+        fn on_clamped_score(&self, node: &NodeId, raw: f64) {
+            self.events
+                .borrow_mut()
+                .push(format!("score {} {}", node, raw));
+        }
+        fn on_unknown_unit(&self, node: &NodeId, raw: &str) {
+            self.events
+                .borrow_mut()
+                .push(format!("unit {} {}", node, raw));
+        }
+        fn on_comment_line(&self, line_no: usize, line: &str) {
+            self.events
+                .borrow_mut()
+                .push(format!("comment {} {}", line_no, line));
+        }
+    }
+
+    #[test]
+    fn test_load_cpvm_nodes_observed() {
+        let contents = format!(
+            "# export\n{}\nOK,Plant,wb,rg,p,10,mg/L,1,m3/s,100,0.5,2,\n\
+             HOT,Plant,wb,rg,p,10,ppm,1,gpm,100,1.5,2,\n\
+             # mid\n\
+             COLD,Plant,wb,rg,p,10,mg/L,1,m3/s,100,-0.1,2,\n",
+            SHARD_HEADER
+        );
+        let path = write_temp_shard("observed.csv", &contents);
+        let observer = RecordingObserver::default();
+        let nodes = load_cpvm_nodes_observed(path.to_str().unwrap(), Some(&observer)).unwrap();
+        let silent = load_cpvm_nodes_observed(path.to_str().unwrap(), None).unwrap();
+        std::fs::remove_file(&path).ok();
+        assert_eq!(nodes, silent);
+        assert_eq!(
+            observer.events.into_inner(),
+            vec![
+                "comment 1 # export",
+                "score HOT 1.5",
+                "unit HOT ppm",
+                "unit HOT gpm",
+                "comment 5 # mid",
+                "score COLD -0.1",
+            ]
+        );
+
+        // Rows before a bad row are reported as they stream in.
+        let contents = format!(
+            "{}\nHOT,Plant,wb,rg,p,10,mg/L,1,m3/s,100,1.5,2,\nBAD,Plant,short\n",
+            SHARD_HEADER
+        );
+        let path = write_temp_shard("observed_bad.csv", &contents);
+        let observer = RecordingObserver::default();
+        assert!(load_cpvm_nodes_observed(path.to_str().unwrap(), Some(&observer)).is_err());
+        std::fs::remove_file(&path).ok();
+        assert_eq!(observer.events.into_inner(), vec!["score HOT 1.5"]);
+    }
+
+    #[test]
+    fn test_compliance_rate() {
+        let configs: Vec<CpvmNodeConfig> = ["A", "B", "C", "D"]
+            .iter()
+            .map(|id| test_config(id))
+            .collect();
+        let safe = configs[0].safety.safe_threshold;
+        let couts = [safe - 1.0, safe, safe + 1.0, safe + 2.0];
+        assert_eq!(compliance_rate(&configs, &couts).unwrap(), 0.5);
+        assert_eq!(
+            compliant_nodes(&configs, &couts).unwrap(),
+            vec![NodeId("A".to_string()), NodeId("B".to_string())]
+        );
+        assert!(compliance_rate(&configs, &couts[..3]).is_err());
+        assert_eq!(compliance_rate(&[], &[]).unwrap(), 0.0);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_load_safety_defaults_toml() {
+        let contents = "[defaults]\ncref = 5.0\nlambda_clf = 10.0\nmu_cbf = 100.0\n\n\
+                        [profiles.PFAS_STRICT]\ncref = 2.0\n\n\
+                        [profiles.FAST]\nlambda_clf = 40.0\nmu_cbf = 50.0\n";
+        let path = write_temp_shard("safety_defaults.toml", contents);
+        let file = load_safety_defaults_toml(path.to_str().unwrap()).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(file.defaults.cref, 5.0);
+        assert_eq!(file.profiles.len(), 2);
+        let strict = file.profiles["PFAS_STRICT"];
+        assert_eq!(
+            (strict.cref, strict.lambda_clf, strict.mu_cbf),
+            (2.0, 10.0, 100.0)
+        );
+        let fast = file.profiles["FAST"];
+        assert_eq!((fast.cref, fast.lambda_clf, fast.mu_cbf), (5.0, 40.0, 50.0));
+
+        let mut meta = test_meta(1.0, FlowUnit::M3PerS);
+        meta.cpvm_profile = "PFAS_STRICT".to_string();
+        assert_eq!(file.bind(meta).safety.cref, 2.0);
+        assert_eq!(file.bind(test_meta(1.0, FlowUnit::M3PerS)).safety.cref, 5.0);
+
+        let path = write_temp_shard("safety_defaults_bad.toml", "[defaults]\ncref = 5.0\n");
+        let err = load_safety_defaults_toml(path.to_str().unwrap()).unwrap_err();
+        std::fs::remove_file(&path).ok();
+        assert_eq!(err.kind(), CpvmErrorKind::Parse);
+    }
+
+    #[test]
+    fn test_evaluate_ecoimpact_reservoir_aware() {
+        let contents = format!(
+            "{},residence_time_s\nRES,Reservoir,wb,rg,p,10,mg/L,1,m3/s,100,1.0,2,25\n\
+             FLAT,Reservoir,wb,rg,p,10,mg/L,1,m3/s,100,1.0,2,\n",
+            REQUIRED_COLUMNS.join(",")
+        );
+        let nodes = load_cpvm_nodes_from_reader(contents.as_bytes()).unwrap();
+        assert_eq!(nodes[0].residence_time_s, Some(25.0));
+        assert_eq!(nodes[1].residence_time_s, None);
+        assert_eq!(effective_horizon(&nodes[0]), 75.0);
+
+        let delayed = bind_cpvm_config(nodes[0].clone(), 5.0, 10.0, 100.0);
+        let flat = bind_cpvm_config(nodes[1].clone(), 5.0, 10.0, 100.0);
+        let with_rt = evaluate_ecoimpact_reservoir_aware(&delayed, 4.0);
+        let without = evaluate_ecoimpact_reservoir_aware(&flat, 4.0);
+        assert!(with_rt.mass_avoided < without.mass_avoided);
+        assert_eq!(
+            without.mass_avoided,
+            evaluate_ecoimpact_for_node(&flat, 4.0).mass_avoided
+        );
+
+        let mut river = nodes[0].clone();
+        river.asset_type = AssetType::RiverReach;
+        assert_eq!(effective_horizon(&river), 100.0);
+        river.asset_type = AssetType::Reservoir;
+        river.residence_time_s = Some(1.0e9);
+        assert_eq!(effective_horizon(&river), 0.0);
+    }
+
+    #[test]
+    fn test_estimate_shard_matches_full_load() {
+        let contents = format!(
+            "{}\n# comment\nA,Plant,wb,North,p,10,mg/L,2,m3/s,100,1.0,2,\n\n\
+             B,Plant,wb,South,p,10,mg/L,500,L/s,100,1.0,2,\n\
+             C,Plant,wb,North,p,10,mg/L,1,m3/s,100,1.0,2,\n",
+            SHARD_HEADER
+        );
+        let path = write_temp_shard("estimate.csv", &contents);
+        let estimate = estimate_shard(path.to_str().unwrap()).unwrap();
+        let nodes = load_cpvm_nodes_from_csv(path.to_str().unwrap()).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        let flow: f64 = nodes
+            .iter()
+            .map(|n| n.q_avg * n.q_unit.to_m3_per_s_factor().unwrap())
+            .sum();
+        let regions: HashSet<&Region> = nodes.iter().map(|n| &n.region).collect();
+        assert_eq!(estimate.row_count, nodes.len());
+        assert!((estimate.total_flow - flow).abs() < 1e-12);
+        assert_eq!(estimate.total_flow, 3.5);
+        assert_eq!(estimate.region_count, regions.len());
+    }
+
+    #[test]
+    fn test_evaluate_by_removal_fraction() {
+        let cfg = test_config("A");
+        assert_eq!(
+            evaluate_by_removal_fraction(&cfg, 1.0).karma_gain,
+            max_karma(&cfg)
+        );
+        assert_eq!(evaluate_by_removal_fraction(&cfg, 0.0).karma_gain, 0.0);
+        assert_eq!(
+            evaluate_by_removal_fraction(&cfg, 1.5).karma_gain,
+            max_karma(&cfg)
+        );
+        assert_eq!(evaluate_by_removal_fraction(&cfg, f64::NAN).karma_gain, 0.0);
+        let half = evaluate_by_removal_fraction(&cfg, 0.5);
+        let direct = evaluate_ecoimpact_for_node(&cfg, cfg.meta.cin_baseline * 0.5);
+        assert_eq!(half.mass_avoided, direct.mass_avoided);
+    }
+
+    #[test]
+    fn test_lat_lon_columns() {
+        let make = |lat: &str, lon: &str| {
+            format!(
+                "{},lat,lon\nA,Plant,wb,rg,p,10,mg/L,1,m3/s,100,1.0,2,{},{}\n",
+                REQUIRED_COLUMNS.join(","),
+                lat,
+                lon
+            )
+        };
+        let nodes = load_cpvm_nodes_from_reader(make("33.45", "-112.07").as_bytes()).unwrap();
+        assert_eq!((nodes[0].lat, nodes[0].lon), (Some(33.45), Some(-112.07)));
+        let nodes = load_cpvm_nodes_from_reader(make("", "").as_bytes()).unwrap();
+        assert_eq!((nodes[0].lat, nodes[0].lon), (None, None));
+        let err = load_cpvm_nodes_from_reader(make("91", "0").as_bytes()).unwrap_err();
+        assert!(
+            err.to_string().contains("lat = 91 must be in [-90, 90]"),
+            "{}",
+            err
+        );
+        assert!(load_cpvm_nodes_from_reader(make("0", "-180.5").as_bytes()).is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_export_geojson() {
+        let mut located = test_meta(1.0, FlowUnit::M3PerS);
+        located.node_id = NodeId("LOC".to_string());
+        located.lat = Some(33.45);
+        located.lon = Some(-112.07);
+        let mut unlocated = test_meta(1.0, FlowUnit::M3PerS);
+        unlocated.lat = Some(10.0);
+        let results = vec![
+            (located, test_result(1.0, 0.5, 7.0)),
+            (unlocated, test_result(1.0, 0.5, 3.0)),
+        ];
+
+        let mut out = Vec::new();
+        export_geojson(&mut out, &results).unwrap();
+        let v: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(v["type"], "FeatureCollection");
+        let features = v["features"].as_array().unwrap();
+        assert_eq!(features.len(), 1);
+        assert_eq!(features[0]["geometry"]["type"], "Point");
+        assert_eq!(
+            features[0]["geometry"]["coordinates"],
+            serde_json::json!([-112.07, 33.45])
+        );
+        assert_eq!(features[0]["properties"]["node_id"], "LOC");
+        assert_eq!(features[0]["properties"]["karma_gain"], 7.0);
+    }
+
+    #[test]
+    fn test_apply_threshold_overrides() {
+        let mut configs: Vec<CpvmNodeConfig> =
+            ["A", "B", "C"].iter().map(|id| test_config(id)).collect();
+        let original = configs[1].safety.safe_threshold;
+        let overrides: HashMap<NodeId, f64> = [("A", 1.5), ("C", 2.5), ("Z", 9.0), ("Y", 8.0)]
+            .iter()
+            .map(|(id, t)| (NodeId(id.to_string()), *t))
+            .collect();
+
+        let (applied, misses) = apply_threshold_overrides(&mut configs, &overrides);
+        assert_eq!(applied, 2);
+        assert_eq!(
+            misses,
+            vec![NodeId("Y".to_string()), NodeId("Z".to_string())]
+        );
+        assert_eq!(configs[0].safety.safe_threshold, 1.5);
+        assert_eq!(configs[1].safety.safe_threshold, original);
+        assert_eq!(configs[2].safety.safe_threshold, 2.5);
+    }
+
+    #[test]
+    fn test_physically_equal_and_merge() {
+        let mut mg = test_meta(1.0, FlowUnit::M3PerS);
+        mg.cin_baseline = 0.002;
+        mg.cin_unit = ConcentrationUnit::MgPerL;
+        let mut ng = mg.clone();
+        ng.cin_baseline = 2000.0;
+        ng.cin_unit = ConcentrationUnit::NgPerL;
+        ng.q_avg = 1000.0;
+        ng.q_unit = FlowUnit::LPerS;
+        ng.notes = "other shard".to_string();
+        assert!(mg.physically_equal(&ng));
+
+        let mut mismatch = ng.clone();
+        mismatch.cin_baseline = 2500.0;
+        assert!(!mg.physically_equal(&mismatch));
+
+        let mut mpn = mg.clone();
+        mpn.cin_unit = ConcentrationUnit::MpnPer100mL;
+        let mut mpn_other = mpn.clone();
+        assert!(mpn.physically_equal(&mpn_other));
+        mpn_other.cin_unit = ConcentrationUnit::MgPerL;
+        assert!(!mpn.physically_equal(&mpn_other));
+
+        let merged = merge_node_sets(
+            vec![vec![mg.clone()], vec![ng]],
+            ConflictPolicy::ErrorUnlessPhysicallyEqual,
+        )
+        .unwrap();
+        assert_eq!(merged, vec![mg.clone()]);
+        assert!(merge_node_sets(
+            vec![vec![mg], vec![mismatch]],
+            ConflictPolicy::ErrorUnlessPhysicallyEqual,
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_format_summary_table() {
+        assert_eq!(format_summary_table(&[]), "no nodes\n");
+
+        let node = |id: &str| {
+            let mut m = test_meta(1.0, FlowUnit::M3PerS);
+            m.node_id = NodeId(id.to_string());
+            m
+        };
+        let results = vec![
+            (node("A"), test_result(1.5, 0.5, 2.0)),
+            (
+                node("PHX-VERY-LONG-NODE-IDENTIFIER"),
+                test_result(12345.678, 0.5, 0.25),
+            ),
+        ];
+        let table = format_summary_table(&results);
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].starts_with("NodeId "));
+        assert!(lines[1].chars().all(|c| c == '-'));
+        assert!(lines.iter().all(|l| l.len() == lines[1].len()), "{}", table);
+        assert!(lines[2].starts_with("A                "));
+        assert!(lines[3].starts_with("PHX-VERY-LONG... "));
+        assert!(lines[2].ends_with("         1.500          2.000"));
+        assert!(lines[3].ends_with("     12345.678          0.250"));
+
+        // Large ng-unit masses widen their column instead of breaking it.
+        let results = vec![
+            (node("A"), test_result(3.6e10, 0.5, 2.0)),
+            (node("B"), test_result(1.5, 0.5, 1.25e12)),
+        ];
+        let table = format_summary_table(&results);
+        let lines: Vec<&str> = table.lines().collect();
+        assert!(lines.iter().all(|l| l.len() == lines[1].len()), "{}", table);
+        assert!(lines[0].ends_with("    MassAvoided             Karma"));
+        assert!(lines[2].ends_with("36000000000.000             2.000"));
+        assert!(lines[3].ends_with("          1.500 1250000000000.000"));
+    }
+
+    #[test]
+    fn test_result_echoes_horizon_and_flow() {
+        let mut meta = test_meta(2.0, FlowUnit::MGD);
+        meta.horizon_s = 86_400.0;
+        let cfg = bind_cpvm_config(meta, 5.0, 10.0, 100.0);
+        let result = evaluate_ecoimpact_for_node(&cfg, 4.0);
+        assert_eq!(result.horizon_s, 86_400.0);
+        assert!((result.q_m3_per_s - 2.0 * 0.043_812_636_388_888_89).abs() < 1e-12);
+
+        let daily = normalize_to_horizon(&result, 86_400.0, 3600.0);
+        assert_eq!(daily.horizon_s, 3600.0);
+        assert_eq!(daily.q_m3_per_s, result.q_m3_per_s);
+    }
+
+    #[test]
+    fn test_aggregate_mass_in_unit() {
+        let node = |region: &str| {
+            let mut m = test_meta(1.0, FlowUnit::M3PerS);
+            m.region = Region::from(region);
+            m
+        };
+        let with_unit = |mass: f64, unit: MassUnit| EcoImpactResult {
+            mass_unit: unit,
+            ..test_result(mass, 0.5, 1.0)
+        };
+        let results = vec![
+            (node("North"), with_unit(2.0e6, MassUnit::Ng)),
+            (node("North"), with_unit(3.0, MassUnit::Mg)),
+            (node("South"), with_unit(1.5, MassUnit::G)),
+        ];
+        let totals = aggregate_mass_in_unit(&results, MassUnit::Mg).unwrap();
+        assert!((totals[&Region::from("North")] - 5.0).abs() < 1e-9);
+        assert!((totals[&Region::from("South")] - 1500.0).abs() < 1e-9);
+
+        let mut bad = results.clone();
+        bad.push((node("South"), with_unit(1.0, MassUnit::Unknown)));
+        let err = aggregate_mass_in_unit(&bad, MassUnit::Mg).unwrap_err();
+        assert!(err.to_string().contains("mass unit unknown"), "{}", err);
+        assert!(aggregate_mass_in_unit(&results, MassUnit::Unknown).is_err());
+    }
+
+    #[test]
+    fn test_config_set_fingerprint() {
+        let configs: Vec<CpvmNodeConfig> =
+            ["A", "B", "C"].iter().map(|id| test_config(id)).collect();
+        let mut reordered = configs.clone();
+        reordered.reverse();
+        assert_eq!(
+            config_set_fingerprint(&configs),
+            config_set_fingerprint(&reordered)
+        );
+
+        let mut noisy = configs.clone();
+        noisy[0].safety.safe_threshold += 1e-14;
+        assert_eq!(
+            config_set_fingerprint(&configs),
+            config_set_fingerprint(&noisy)
+        );
+
+        let mut changed = configs.clone();
+        changed[1].safety.safe_threshold += 0.5;
+        assert_ne!(
+            config_set_fingerprint(&configs),
+            config_set_fingerprint(&changed)
+        );
+        assert_ne!(
+            config_set_fingerprint(&configs),
+            config_set_fingerprint(&configs[..2])
+        );
+    }
+
+    #[test]
+    fn test_load_cout_setpoints_and_evaluate() {
+        let setpoints = "node_id,cout,cout_unit\nA,4,mg/L\nB,2000,ng/L\nC,,\nD\n,5,mg/L\n";
+        let sp_path = write_temp_shard("setpoints.csv", setpoints);
+        let loaded = load_cout_setpoints(sp_path.to_str().unwrap()).unwrap();
+        assert_eq!(loaded.len(), 2);
+        assert_eq!(
+            loaded[&NodeId("A".to_string())],
+            (4.0, ConcentrationUnit::MgPerL)
+        );
+        assert_eq!(
+            loaded[&NodeId("B".to_string())],
+            (2000.0, ConcentrationUnit::NgPerL)
+        );
+        assert!(!loaded.contains_key(&NodeId("C".to_string())));
+        assert!(!loaded.contains_key(&NodeId("D".to_string())));
+        assert!(!loaded.contains_key(&NodeId(String::new())));
+
+        let shard = format!(
+            "{}\nA,Plant,wb,rg,p,10,mg/L,1,m3/s,100,1.0,2,\n\
+             B,Plant,wb,rg,p,10,mg/L,1,m3/s,100,1.0,2,\n\
+             C,Plant,wb,rg,p,10,mg/L,1,m3/s,100,1.0,2,\n",
+            SHARD_HEADER
+        );
+        let shard_path = write_temp_shard("setpoints_shard.csv", &shard);
+        let defaults = SafetyDefaults {
+            cref: 5.0,
+            lambda_clf: 10.0,
+            mu_cbf: 100.0,
+        };
+        let results = evaluate_shard_with_setpoints(
+            shard_path.to_str().unwrap(),
+            sp_path.to_str().unwrap(),
+            defaults,
+        )
+        .unwrap();
+        std::fs::remove_file(&sp_path).ok();
+        std::fs::remove_file(&shard_path).ok();
+        assert_eq!(results[0].1.mass_avoided, 600.0);
+        assert!((results[1].1.mass_avoided - (10.0 - 0.002) * 100.0).abs() < 1e-9);
+        assert_eq!(results[2].1.mass_avoided, 0.0);
+    }
+
+    #[test]
+    fn test_compute_mass_avoided_overflow() {
+        let err = try_compute_mass_avoided(1.0e200, 0.0, 1.0e100, 1.0e10).unwrap_err();
+        assert!(err.to_string().contains("overflow"), "{}", err);
+        // Finite but above the limit.
+        assert!(try_compute_mass_avoided(f64::MAX / 1.5, 0.0, 1.0, 1.0).is_err());
+        // `cin - cout` itself overflows.
+        assert_eq!(
+            compute_mass_avoided_signed(f64::MAX, -f64::MAX, 1.0, 1.0),
+            0.0
+        );
+        assert_eq!(compute_mass_avoided(1.0e200, 0.0, 1.0e100, 1.0e10), 0.0);
+        assert!(try_compute_mass_avoided(MAX_MASS_AVOIDED, 0.0, 1.0, 1.0).is_ok());
+    }
+
+    #[test]
+    fn test_asset_type_census() {
+        let node = |asset: AssetType| {
+            let mut m = test_meta(1.0, FlowUnit::M3PerS);
+            m.asset_type = asset;
+            m
+        };
+        let nodes = vec![
+            node(AssetType::Plant),
+            node(AssetType::Reservoir),
+            node(AssetType::Other("Wetland".to_string())),
+            node(AssetType::Plant),
+            node(AssetType::Other("Canal".to_string())),
+            node(AssetType::Plant),
+            node(AssetType::Reservoir),
+            node(AssetType::Other("Canal".to_string())),
+        ];
+        assert_eq!(
+            asset_type_census(&nodes),
+            vec![
+                (AssetType::Plant, 3),
+                (AssetType::Other("Canal".to_string()), 2),
+                (AssetType::Reservoir, 2),
+                (AssetType::Other("Wetland".to_string()), 1),
+            ]
+        );
+        assert!(asset_type_census(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_column_aliases() {
+        let contents = "id,typ,waterbody,region,cpvm_profile,cin_baseline,cin_unit,q,q_unit,\
+                        horizon_s,ecoimpactscore,karma_per_unit,bemerkung\n\
+                        A,Plant,wb,rg,p,10,mg/L,2.5,m3/s,100,1.0,2,hallo\n";
+        let path = write_temp_shard("column_aliases.csv", contents);
+        let path = path.to_str().unwrap();
+        assert!(load_cpvm_nodes_from_csv(path).is_err());
+
+        let aliases = ColumnAliases::default()
+            .with_alias("node_id", "id")
+            .with_alias("asset_type", "typ")
+            .with_alias("q_avg", "q")
+            .with_alias("notes", "bemerkung");
+        assert_eq!(aliases.accepted("q_avg"), ["q_avg", "q"]);
+        let opts = LoaderOptions {
+            column_aliases: aliases,
+            ..LoaderOptions::default()
+        };
+        let nodes = load_cpvm_nodes_with_options(path, opts).unwrap();
+        std::fs::remove_file(path).ok();
+        assert_eq!(nodes[0].node_id, NodeId("A".to_string()));
+        assert_eq!(nodes[0].asset_type, AssetType::Plant);
+        assert_eq!(nodes[0].q_avg, 2.5);
+        assert_eq!(nodes[0].notes, "hallo");
+    }
+
+    #[test]
+    fn test_karma_envelope() {
+        let mut cfg = test_config("A");
+        cfg.meta.cin_baseline = 10.0;
+        cfg.safety.safe_threshold = 8.0;
+        let (min, max) = karma_envelope(&cfg, 2.0, 6.0);
+        assert!(min < max);
+        assert_eq!(min, evaluate_ecoimpact_for_node(&cfg, 6.0).karma_gain);
+        assert_eq!(max, evaluate_ecoimpact_for_node(&cfg, 2.0).karma_gain);
+        assert_eq!(karma_envelope(&cfg, 6.0, 2.0), (min, max));
+
+        // Bounds above the safe threshold and baseline are clamped.
+        let (min, max) = karma_envelope(&cfg, -5.0, 50.0);
+        assert_eq!(min, evaluate_ecoimpact_for_node(&cfg, 8.0).karma_gain);
+        assert_eq!(max, max_karma(&cfg));
+        cfg.safety.safe_threshold = 20.0;
+        assert_eq!(karma_envelope(&cfg, 12.0, 50.0), (0.0, 0.0));
+    }
+
+    #[test]
+    fn test_extra_trim_chars() {
+        let contents = format!(
+            "{}\nA,Plant,wb,rg,p,\u{00A0}3.5\u{00A0},mg/L,\u{200B}2\u{200B},m3/s,\
+             \u{FEFF}24h,1.0,2,\n",
+            SHARD_HEADER
+        );
+        assert!(load_cpvm_nodes_from_reader(contents.as_bytes()).is_err());
+
+        let opts = LoaderOptions {
+            extra_trim_chars: vec!['\u{00A0}', '\u{200B}', '\u{FEFF}'],
+            ..LoaderOptions::default()
+        };
+        assert_eq!(opts.trim_numeric("\u{00A0}3.5\u{00A0}"), "3.5");
+        let node = CpvmNodeReader::with_options(contents.as_bytes(), opts)
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(node.cin_baseline, 3.5);
+        assert_eq!(node.q_avg, 2.0);
+        assert_eq!(node.horizon_s, 86_400.0);
+    }
+
+    #[test]
+    fn test_parse_f64_loose_rejects_misplaced_thousands() {
+        for bad in [
+            "3,5", "1,23", "1,2345", ",123", "1,,234", "1.5,3", "1e3,000",
+        ] {
+            let err = parse_f64_loose(bad).unwrap_err().to_string();
+            assert!(err.contains("invalid number"), "{}: {}", bad, err);
+        }
+        assert!(parse_f64_loose_with("3.5", ',').is_err());
+        assert_eq!(parse_f64_loose_with("3,5", ',').unwrap(), 3.5);
+    }
+
+    #[test]
+    fn test_load_collect_errors_header_line_and_io_stop() {
+        let contents = "# exported\n\nnode_id,asset_type\nA,Plant\n";
+        let path = write_temp_shard("collect_errors_header", contents);
+        let (nodes, errors) = load_cpvm_nodes_collect_errors(path.to_str().unwrap());
+        std::fs::remove_file(&path).ok();
+        assert!(nodes.is_empty());
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].0, 3);
+
+        let mut bytes =
+            format!("{}\nA,Plant,W,R,P,1,mg/L,1,m3/s,60,0.5,1,\n", SHARD_HEADER).into_bytes();
+        bytes.extend_from_slice(b"B,\xff\xfe,W\n\xffC\n");
+        bytes.extend_from_slice(b"D,Plant,W,R,P,1,mg/L,1,m3/s,60,0.5,1,\n");
+        let path = std::env::temp_dir().join(format!("cpvm_collect_io_{}.csv", std::process::id()));
+        std::fs::write(&path, bytes).unwrap();
+        let (nodes, errors) = load_cpvm_nodes_collect_errors(path.to_str().unwrap());
+        std::fs::remove_file(&path).ok();
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].0, 3);
+        assert_eq!(errors[0].1.kind(), CpvmErrorKind::Io);
+    }
+
+    #[test]
+    fn test_unitless_setpoint_uses_node_cin_unit() {
+        let setpoints = "node_id,cout\nN,2000\n";
+        let sp_path = write_temp_shard("setpoints_unitless.csv", setpoints);
+        let loaded = load_cout_setpoints(sp_path.to_str().unwrap()).unwrap();
+        assert_eq!(
+            loaded[&NodeId("N".to_string())],
+            (2000.0, ConcentrationUnit::MgPerL)
+        );
+
+        let shard = format!(
+            "{}\nN,Plant,wb,rg,p,10000,ng/L,1,m3/s,100,1.0,2,\n",
+            SHARD_HEADER
+        );
+        let shard_path = write_temp_shard("setpoints_unitless_shard.csv", &shard);
+        let defaults = SafetyDefaults {
+            cref: 5.0,
+            lambda_clf: 10.0,
+            mu_cbf: 100.0,
+        };
+        let results = evaluate_shard_with_setpoints(
+            shard_path.to_str().unwrap(),
+            sp_path.to_str().unwrap(),
+            defaults,
+        )
+        .unwrap();
+        std::fs::remove_file(&sp_path).ok();
+        std::fs::remove_file(&shard_path).ok();
+        // 2000 ng/L, not 2000 mg/L: C_out stays below C_in.
+        assert!((results[0].1.mass_avoided - (10000.0 - 2000.0) * 100.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_extra_trim_chars_only_cell_is_blank() {
+        let contents = format!(
+            "{},sec_name,sec_cin,sec_unit,lat,lon\n\
+             A,Plant,wb,rg,p,1,mg/L,1,m3/s,60,0.5,1,,\u{FEFF},,,\u{200B},\u{FEFF}\u{200B}\n",
+            SHARD_HEADER
+        );
+        assert!(load_cpvm_nodes_from_reader(contents.as_bytes()).is_err());
+
+        let opts = LoaderOptions {
+            extra_trim_chars: vec!['\u{200B}', '\u{FEFF}'],
+            ..LoaderOptions::default()
+        };
+        let node = CpvmNodeReader::with_options(contents.as_bytes(), opts)
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap();
+        assert!(node.secondary.is_none());
+        assert_eq!(node.lat, None);
+        assert_eq!(node.lon, None);
+    }
+
+    #[test]
+    fn test_whitespace_only_optional_cells_without_trim_fields() {
+        let contents = format!(
+            "{},sec_name,sec_cin,sec_unit,residence_time_s,lat,lon\n\
+             A,Plant,wb,rg,p,1,mg/L,1,m3/s,60,0.5,1,,  ,, , ,\t, \n",
+            SHARD_HEADER
+        );
+        let opts = LoaderOptions {
+            trim_fields: false,
+            ..LoaderOptions::default()
+        };
+        let node = CpvmNodeReader::with_options(contents.as_bytes(), opts)
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap();
+        assert!(node.secondary.is_none());
+        assert_eq!(node.residence_time_s, None);
+        assert_eq!(node.lat, None);
+        assert_eq!(node.lon, None);
     }
 }